@@ -1,29 +1,70 @@
-use super::{app_initializer, cli, Config};
+use super::{app_initializer, cli, ipc, poller, Config};
 use crate::commands::{
     Command, DrawCommand, KeyPressCommand, LeftClickCommand, LeftReleaseCommand,
     MiddleClickCommand, MotionCommand, RightClickCommand, ScrollWheelDownCommand,
     ScrollWheelUpCommand,
 };
 use crate::domain::error::PincelError;
+use crate::domain::event_handler::copy_desktop_image;
 use crate::domain::{entities, Result};
 use app_initializer::AtomCollection;
+use chrono::Utc;
 use cli::Cli;
-use entities::{color::CurrentColorSingleton, movement::Movement, LineWidth};
+use entities::{
+    color::CurrentColorSingleton, movement::Movement, Background, Blending, Eraser, Fill, History,
+    Laser, Layer, Lens, LineWidth, Overlay, Selection, Session, Spotlight, Tool,
+};
+use ipc::{Ipc, Request};
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::{
     ButtonPressEvent, ButtonReleaseEvent, ClientMessageEvent, ConnectionExt, EnterNotifyEvent,
-    ExposeEvent, InputFocus, KeyPressEvent, MotionNotifyEvent,
+    ExposeEvent, InputFocus, KeyPressEvent, MotionNotifyEvent, Rectangle, BUTTON_PRESS_EVENT,
+    KEY_PRESS_EVENT,
 };
 use x11rb::protocol::Event;
 use x11rb::CURRENT_TIME;
 
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
 pub struct Application<C> {
     pub is_running: bool,
     pub skip_frame: bool,
     pub stack: Vec<Option<Movement>>,
     pub brush_color: CurrentColorSingleton,
     pub line_width: LineWidth,
+    pub tool: Tool,
+    pub last_tool: Tool,
+    pub fill: Fill,
+    pub shape_origin: (i16, i16),
+    pub vertices: Vec<(i16, i16)>,
+    pub last_click: u32,
+    pub eraser: Eraser,
+    pub laser: Laser,
+    pub spotlight: Spotlight,
+    pub lens: Lens,
+    pub background: Background,
+    pub last_frame: Instant,
+    pub selection: Selection,
+    pub history: History,
+    pub clipboard: Vec<Movement>,
+    pub layers: Vec<Layer>,
+    pub current_layer: usize,
+    pub marker_counter: u32,
+    /// The file the drawings were loaded from or last saved to
+    pub session_path: Option<PathBuf>,
+    pub is_auto_fading: bool,
+    pub overlay: Overlay,
     pub conn: C,
+    pub conn_fd: RawFd,
+    /// The socket scripts control pincel through, when it could be bound
+    pub ipc: Option<Ipc>,
+    pub depth: u8,
+    pub blending: Blending,
+    /// The part of the root window the overlay covers
+    pub area: Rectangle,
     pub screen_num: usize,
     pub win_id: u32,
     pub gc_id: u32,
@@ -36,17 +77,176 @@ pub struct Application<C> {
 impl<C: Connection + Send + Sync> Application<C> {
     pub fn run(&mut self) -> Result {
         while self.is_running {
-            self.reset_frame();
-            let event = self.conn.wait_for_event()?;
-            // FIXME: handle input focus lost
-            self.dispatch(event)?;
-            if self.skip_frame {
-                continue;
-            };
+            // events can be queued already, without the
+            // socket becoming readable again for them
+            while let Some(event) = self.conn.poll_for_event()? {
+                self.reset_frame();
+                // FIXME: handle input focus lost
+                self.dispatch(event)?;
+                if !self.is_running {
+                    return Ok(());
+                }
+            }
+            self.animate()?;
+            self.conn.flush()?;
+
+            let mut fds = vec![self.conn_fd];
+            fds.extend(self.ipc.iter().flat_map(Ipc::fds));
+            let ready = poller::wait(&fds, self.next_timeout())?;
+            if self
+                .ipc
+                .as_ref()
+                .map_or(false, |ipc| ipc.fds().iter().any(|fd| ready.contains(fd)))
+            {
+                self.serve_requests()?;
+            }
         }
         Ok(())
     }
 
+    /// Answers the clients of the socket, which
+    /// is put back once they have been served
+    fn serve_requests(&mut self) -> Result {
+        if let Some(mut ipc) = self.ipc.take() {
+            let served = ipc.serve(|line| self.request(line));
+            self.ipc = Some(ipc);
+            served?;
+            self.conn.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Saves the drawings to the given file, or the one they were
+    /// last saved to or loaded from, or a new one next to the screenshots
+    pub fn save_session(&mut self, path: Option<&str>) -> Result {
+        let path = match (path, &self.session_path) {
+            (Some(path), _) => PathBuf::from(path),
+            (None, Some(path)) => path.clone(),
+            (None, None) => {
+                let current_date_time = Utc::now().format("%Y-%m-%d %H:%M:%S");
+                std::env::home_dir()
+                    .unwrap_or_default()
+                    .join(&self.config.screenshot_dir)
+                    .join(format!("Session from {current_date_time}.json"))
+            }
+        };
+
+        Session::new(&self.stack, &self.layers).save(&path)?;
+        println!("Saved the drawings to {}", path.display());
+        self.session_path = Some(path);
+        Ok(())
+    }
+
+    /// Carries out a request as the key or
+    /// button press it stands for would
+    fn request(&mut self, line: &str) -> std::result::Result<(), String> {
+        self.reset_frame();
+        let done = match Request::parse(line)? {
+            Request::Show(_) if self.overlay != Overlay::Hidden => Ok(()),
+            Request::Key(detail) | Request::Show(detail) => {
+                let event = self.remote_key_press(detail);
+                KeyPressCommand::new(self, event).execute()
+            }
+            Request::Button(detail) => {
+                let event = self.remote_button_press(detail);
+                self.handle_buttons(event)
+            }
+            Request::Screenshot(path) => copy_desktop_image(&path),
+            Request::Save(path) => self.save_session(path.as_deref()),
+            Request::Quit => {
+                self.is_running = false;
+                Ok(())
+            }
+        };
+        done.map_err(|e| e.to_string())
+    }
+
+    fn remote_key_press(&self, detail: u8) -> KeyPressEvent {
+        KeyPressEvent {
+            response_type: KEY_PRESS_EVENT,
+            detail,
+            sequence: 0,
+            time: CURRENT_TIME,
+            root: self.conn.setup().roots[self.screen_num].root,
+            event: self.win_id,
+            child: x11rb::NONE,
+            root_x: 0,
+            root_y: 0,
+            event_x: 0,
+            event_y: 0,
+            state: 0,
+            same_screen: true,
+        }
+    }
+
+    fn remote_button_press(&self, detail: u8) -> ButtonPressEvent {
+        ButtonPressEvent {
+            response_type: BUTTON_PRESS_EVENT,
+            detail,
+            sequence: 0,
+            time: CURRENT_TIME,
+            root: self.conn.setup().roots[self.screen_num].root,
+            event: self.win_id,
+            child: x11rb::NONE,
+            root_x: 0,
+            root_y: 0,
+            event_x: 0,
+            event_y: 0,
+            state: 0,
+            same_screen: true,
+        }
+    }
+
+    /// Returns how long to wait for events before a timer is due,
+    /// or nothing when there's no timer to wait for
+    fn next_timeout(&self) -> Option<Duration> {
+        let frame = Some(FRAME_INTERVAL.saturating_sub(self.last_frame.elapsed()))
+            .filter(|_| self.laser.is_fading());
+        let expiry = self
+            .stack
+            .iter()
+            .flatten()
+            .filter_map(|mov| mov.expires)
+            .min()
+            .map(|expires| expires.saturating_duration_since(Instant::now()));
+
+        match (frame, expiry) {
+            (Some(frame), Some(expiry)) => Some(frame.min(expiry)),
+            (frame, expiry) => frame.or(expiry),
+        }
+    }
+
+    /// Fades the laser trails and removes the expired drawings,
+    /// redrawing the screen when a frame is due or anything expired
+    fn animate(&mut self) -> Result {
+        let is_frame_due = self.laser.is_fading() && self.last_frame.elapsed() >= FRAME_INTERVAL;
+        if !self.expire() && !is_frame_due {
+            return Ok(());
+        }
+        self.last_frame = Instant::now();
+        self.laser.fade();
+        self.conn.clear_area(true, self.win_id, 0, 0, 0, 0)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Removes the drawings whose lifetime is over,
+    /// returning whether there was any of them
+    fn expire(&mut self) -> bool {
+        let now = Instant::now();
+        let len = self.stack.len();
+        self.selection.retain(&mut self.stack, |mov| {
+            mov.as_ref()
+                .and_then(|mov| mov.expires)
+                .map_or(true, |expires| expires > now)
+        });
+        if self.stack.len() == len {
+            return false;
+        }
+        self.current = self.stack.len().saturating_sub(1);
+        true
+    }
+
     pub fn reset_frame(&mut self) {
         self.skip_frame = false;
     }
@@ -83,6 +283,12 @@ impl<C: Connection + Send + Sync> Application<C> {
     fn handle_button_press(&mut self, e: ButtonPressEvent) -> Result {
         self.conn
             .set_input_focus(InputFocus::Parent, self.win_id, CURRENT_TIME)?;
+        self.handle_buttons(e)
+    }
+
+    /// Runs the commands for a button, which can also be asked
+    /// for through the socket while the overlay is hidden
+    fn handle_buttons(&mut self, e: ButtonPressEvent) -> Result {
         LeftClickCommand::new(self, e).execute()?;
         MiddleClickCommand::new(self, e).execute()?;
         RightClickCommand::new(self, e).execute()?;
@@ -1,15 +1,31 @@
-use super::{app::Application, cli::Cli, config::Config};
+use super::{app::Application, cli::Cli, config::Config, ipc::Ipc};
 use crate::{
-    domain::{CurrentColorSingleton, GraphicContext, LineWidth, Movement, PincelError},
+    domain::{
+        Background, Blending, CurrentColorSingleton, Eraser, Fill, GraphicContext, History, Laser,
+        Layer, Lens, LineWidth, Movement, Overlay, Pattern, PincelError, Selection, Session, Shape,
+        Spotlight, Tool,
+    },
     gui::window_builder::WindowBuilder,
 };
 
 use x11rb::wrapper::ConnectionExt as _;
 
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::time::Instant;
 use x11rb::connection::Connection;
-use x11rb::protocol::xproto::{free_colormap, AtomEnum, ConnectionExt, InputFocus, PropMode};
+use x11rb::protocol::randr::ConnectionExt as _;
+use x11rb::protocol::xproto::{
+    free_colormap, AtomEnum, ChangeWindowAttributesAux, ConnectionExt, GrabMode, InputFocus,
+    ModMask, PropMode, Rectangle, Screen, Visualtype,
+};
+use x11rb::rust_connection::RustConnection;
 use x11rb::{atom_manager, CURRENT_TIME};
 
+const WHITE: u32 = 0xffff_ffff;
+const PASSTHROUGH_KEY: u8 = 74;
+const DAEMON_KEY: u8 = 75;
+
 // A collection of the atoms we will need.
 atom_manager! {
     pub AtomCollection: AtomCollectionCookie {
@@ -22,43 +38,42 @@ atom_manager! {
     }
 }
 
-pub fn init() -> std::result::Result<Application<impl Connection + Send + Sync>, PincelError> {
-    let (conn, screen_num) = x11rb::connect(None)?;
+#[allow(clippy::too_many_lines)]
+pub fn init(
+    mut cli: Cli,
+) -> std::result::Result<Application<impl Connection + Send + Sync>, PincelError> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let conn_fd = conn.stream().as_raw_fd();
 
     let screen = &conn.setup().roots[screen_num];
     let win_id = conn.generate_id()?;
     let gc_id = conn.generate_id()?;
     let atoms = AtomCollection::new(&conn)?.reply()?;
 
-    // fetch all depths
-    let depths = &screen.allowed_depths;
-
-    // fetch a visual supporting alpha channels
-    let visual = GraphicContext::fetch_visual_alpha(depths)?;
+    let (visual, depth) = choose_visual(&conn, screen_num, &mut cli)?;
 
-    let win_start_x = 0;
-    let win_start_y = 0;
+    let area = monitor_area(&conn, screen, cli.monitor.as_deref());
 
     let colormap = GraphicContext::genarate_colormap(&conn, screen, &visual)?;
 
-    let (width, height) = (
-        f32::from(screen.width_in_pixels) as u16,
-        f32::from(screen.height_in_pixels) as u16,
-    );
-
     WindowBuilder::new()
         .with_win_id(win_id)
         .with_screen(screen)
-        .with_pos((&win_start_x, &win_start_y))
-        .with_size((&width, &height))
+        .with_pos((&area.x, &area.y))
+        .with_size((&area.width, &area.height))
         .with_visual(&visual)
+        .with_depth(depth)
         .with_win_params(colormap)
         .build(&conn)?;
 
-    let brush_color = CurrentColorSingleton::new();
+    let mut brush_color = CurrentColorSingleton::new();
+    if let Some(color) = &cli.color {
+        brush_color.set(color);
+    }
 
-    let line_width = LineWidth::Thin;
-    let gc_aux = GraphicContext::create_gc(brush_color.value(), line_width);
+    let line_width = cli.width.unwrap_or(LineWidth::Thin);
+    let font = GraphicContext::open_font(&conn)?;
+    let gc_aux = GraphicContext::create_gc(brush_color.value(), line_width, font);
 
     free_colormap(&conn, colormap)?;
 
@@ -88,15 +103,48 @@ pub fn init() -> std::result::Result<Application<impl Connection + Send + Sync>,
 
     conn.create_gc(gc_id, win_id, &gc_aux)?;
 
-    conn.map_window(win_id)?;
-    conn.set_input_focus(InputFocus::PointerRoot, win_id, CURRENT_TIME)?;
+    apply_flags(&cli, &conn, (screen, area), (win_id, depth), gc_id)?;
+    // nothing blends the alpha of the colors over a still copy of the screen
+    let blending = if cli.is_frozen {
+        Blending::dither(&conn, win_id)?
+    } else {
+        Blending::Alpha
+    };
 
     conn.flush()?;
 
-    let stack: Vec<Option<Movement>> = Vec::new();
-    let current = stack.len();
     println!("Press q to exit.");
 
+    let mut config = Config::new(cli.config.as_deref());
+    if let Some(dir) = &cli.screenshot_dir {
+        config.screenshot_dir = dir.display().to_string();
+    }
+    let mut background = Background::new(
+        config.board_color,
+        Pattern::from(config.board_pattern.as_str()),
+    );
+    background.board = cli.board.unwrap_or(background.board);
+    let (layers, mut stack, session_path) = load_session(&cli, &config);
+    for mov in stack.iter_mut().flatten() {
+        mov.measure(&conn, gc_id)?;
+    }
+    let current = stack.len().saturating_sub(1);
+    // the next marker counts on from the loaded ones
+    let marker_counter = stack
+        .iter()
+        .flatten()
+        .filter_map(|mov| match mov.shape {
+            Shape::Marker(number) => Some(number.saturating_add(1)),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(1);
+
+    // pincel still works without being scriptable
+    let ipc = Ipc::bind()
+        .map_err(|e| println!("Could not listen on {}: {e}", Ipc::path().display()))
+        .ok();
+
     let app = Application {
         is_running: true,
         stack,
@@ -104,13 +152,209 @@ pub fn init() -> std::result::Result<Application<impl Connection + Send + Sync>,
         gc_id,
         brush_color,
         line_width,
+        tool: cli.tool.unwrap_or(Tool::Brush),
+        last_tool: Tool::Brush,
+        fill: Fill::new(),
+        shape_origin: (0, 0),
+        vertices: Vec::new(),
+        last_click: 0,
+        eraser: Eraser::new(),
+        laser: Laser::new(),
+        spotlight: Spotlight::new(),
+        lens: Lens::new(),
+        background,
+        last_frame: Instant::now(),
+        selection: Selection::new(),
+        history: History::new(),
+        clipboard: Vec::new(),
+        layers,
+        current_layer: 0,
+        marker_counter,
+        session_path,
+        is_auto_fading: false,
+        overlay: if cli.starts_hidden() {
+            Overlay::Hidden
+        } else {
+            Overlay::Drawing
+        },
         conn,
+        conn_fd,
+        ipc,
+        depth,
+        blending,
+        area,
         screen_num,
         skip_frame: false,
         current,
         atoms,
-        cli: Cli::new(),
-        config: Config::new(),
+        cli,
+        config,
     };
     Ok(app)
 }
+
+/// Returns a visual with an alpha channel and its depth, or the root one
+/// when there is none, freezing the screen when the window can't be seen through
+fn choose_visual(
+    conn: &(impl Connection + Send + Sync),
+    screen_num: usize,
+    cli: &mut Cli,
+) -> Result<(Visualtype, u8), PincelError> {
+    let screen = &conn.setup().roots[screen_num];
+    let visual = GraphicContext::fetch_visual_alpha(&screen.allowed_depths);
+    let (visual, depth) = if let Some(visual) = visual {
+        (visual, 32)
+    } else {
+        println!("No visual with an alpha channel was found");
+        cli.is_frozen = true;
+        (
+            GraphicContext::fetch_visual_root(screen)?,
+            screen.root_depth,
+        )
+    };
+
+    if !cli.is_frozen && !GraphicContext::is_composited(conn, screen_num)? {
+        println!("No compositing manager is running");
+        cli.is_frozen = true;
+    }
+    if cli.is_frozen {
+        println!("Drawing over a still copy of the screen");
+    }
+    Ok((visual, depth))
+}
+
+/// Sets up the window as asked on the command line
+fn apply_flags(
+    cli: &Cli,
+    conn: &(impl Connection + Send + Sync),
+    (screen, area): (&Screen, Rectangle),
+    (win_id, depth): (u32, u8),
+    gc_id: u32,
+) -> Result<(), PincelError> {
+    // draw over a still copy of the screen, which doesn't need
+    // a compositor to be seen through, or a white board if
+    // the screen can't be copied
+    if cli.is_frozen {
+        if let Err(e) = GraphicContext::freeze(conn, (screen.root, area), (win_id, depth), gc_id) {
+            println!("Could not copy the screen, drawing on a white board: {e}");
+            conn.change_window_attributes(
+                win_id,
+                &ChangeWindowAttributesAux::new().background_pixel(WHITE),
+            )?;
+        }
+    }
+
+    // the overlay doesn't get the keys while the clicks go through
+    // it or it's hidden, so their toggles are taken from every window
+    let keys = [(PASSTHROUGH_KEY, "F8"), (DAEMON_KEY, "F9")];
+    let mut has_daemon_key = false;
+    for (key, name) in keys {
+        // another program can have the key already
+        let grab = conn.grab_key(
+            true,
+            screen.root,
+            ModMask::Any,
+            key,
+            GrabMode::Async,
+            GrabMode::Async,
+        )?;
+        match grab.check() {
+            Ok(()) => has_daemon_key |= key == DAEMON_KEY,
+            Err(e) => println!("Could not take {name} from the other windows: {e:?}"),
+        }
+    }
+
+    // the hidden overlay waits for its hotkey to be shown
+    if cli.starts_hidden() {
+        if has_daemon_key {
+            println!("Press F9 to draw.");
+        } else {
+            println!("Run pincel toggle to draw.");
+        }
+    } else {
+        conn.map_window(win_id)?;
+        conn.set_input_focus(InputFocus::PointerRoot, win_id, CURRENT_TIME)?;
+    }
+    Ok(())
+}
+
+/// Returns the layers of the configuration, with no drawings, or the
+/// drawings and layers saved to the file given on the command line
+fn load_session(
+    cli: &Cli,
+    config: &Config,
+) -> (Vec<Layer>, Vec<Option<Movement>>, Option<PathBuf>) {
+    let layers = config
+        .layers
+        .iter()
+        .map(|name| Layer::new(name))
+        .collect::<Vec<Layer>>();
+    let path = match &cli.load {
+        Some(path) => path,
+        None => return (layers, Vec::new(), None),
+    };
+
+    match Session::load(path) {
+        Ok(session) => (
+            session.layers.clone(),
+            session.movements(),
+            Some(path.clone()),
+        ),
+        Err(e) => {
+            println!("Could not load the drawings from {}: {e}", path.display());
+            (layers, Vec::new(), None)
+        }
+    }
+}
+
+/// Returns the part of the root window to cover, the
+/// whole of it or the monitor asked for on the command line
+fn monitor_area(
+    conn: &(impl Connection + Send + Sync),
+    screen: &Screen,
+    monitor: Option<&str>,
+) -> Rectangle {
+    let whole = Rectangle {
+        x: 0,
+        y: 0,
+        width: screen.width_in_pixels,
+        height: screen.height_in_pixels,
+    };
+    let monitor = match monitor {
+        Some(monitor) => monitor,
+        None => return whole,
+    };
+
+    match find_monitor(conn, screen.root, monitor) {
+        Ok(Some(area)) => area,
+        Ok(None) => {
+            println!("No monitor {monitor} was found, covering the whole screen");
+            whole
+        }
+        Err(e) => {
+            println!("Could not list the monitors, covering the whole screen: {e}");
+            whole
+        }
+    }
+}
+
+/// Looks a monitor up by its name, like "HDMI-1", or its number
+fn find_monitor(
+    conn: &(impl Connection + Send + Sync),
+    root: u32,
+    monitor: &str,
+) -> Result<Option<Rectangle>, PincelError> {
+    let monitors = conn.randr_get_monitors(root, true)?.reply()?.monitors;
+    for (index, info) in monitors.iter().enumerate() {
+        let name = conn.get_atom_name(info.name)?.reply()?.name;
+        if monitor == index.to_string() || monitor.as_bytes() == name.as_slice() {
+            return Ok(Some(Rectangle {
+                x: info.x,
+                y: info.y,
+                width: info.width,
+                height: info.height,
+            }));
+        }
+    }
+    Ok(None)
+}
@@ -1,10 +1,278 @@
+use crate::domain::{Board, CurrentColor, LineWidth, Tool};
+
+use std::convert::TryFrom;
+use std::env;
+use std::fs::File;
+use std::path::PathBuf;
+
+const FREEZE_FLAG: &str = "--freeze";
+const DAEMON_FLAG: &str = "--daemon";
+const HIDDEN_FLAG: &str = "--hidden";
+const COLOR_FLAG: &str = "--color";
+const WIDTH_FLAG: &str = "--width";
+const TOOL_FLAG: &str = "--tool";
+const BACKGROUND_FLAG: &str = "--background";
+const MONITOR_FLAG: &str = "--monitor";
+const CONFIG_FLAG: &str = "--config";
+const SCREENSHOT_DIR_FLAG: &str = "--screenshot-dir";
+const LOAD_FLAG: &str = "--load";
+const OUTPUT_FLAGS: [&str; 2] = ["--output", "-o"];
+const HELP_FLAGS: [&str; 2] = ["--help", "-h"];
+
+pub const USAGE: &str = "\
+Usage: pincel [COMMAND] [OPTIONS]
+
+Commands:
+  start                  Open the overlay, the default
+  toggle                 Show or hide the drawings
+  show                   Show the drawings, if they are hidden
+  clear                  Clear all drawings
+  undo                   Undo the last change
+  color <COLOR>          Change the brush color: red, blue, yellow, green, orange or black
+  width <WIDTH>          Change the line width: thicker or thinner
+  tool <TOOL>            Change the tool: brush, eraser, select, marker, rectangle,
+                         ellipse, polygon, laser, spotlight or zoom
+  screenshot             Take a screenshot
+  save                   Save the drawings, to the file they were loaded
+                         from or last saved to, or a new one
+  quit                   Close pincel
+  help                   Print this message
+
+Options for start, overriding pincel.conf:
+  --freeze               Draw over a still copy of the screen
+  --daemon               Keep running in the background, shown and hidden with F9
+  --hidden               Start with the drawings hidden, shown with F9 or pincel toggle
+  --color <COLOR>        The brush color to start with
+  --width <WIDTH>        The line width to start with: thin, normal or wide
+  --tool <TOOL>          The tool to start with
+  --background <BOARD>   The background to start with: transparent, white or dark
+  --monitor <MONITOR>    Cover only a monitor, by its name or number from 0
+  --config <PATH>        Read the configuration from another file
+  --screenshot-dir <DIR> Where to save the screenshots
+  --load <PATH>          Open drawings saved before
+
+Options for screenshot and save:
+  -o, --output <PATH>    Where to save the screenshot or the drawings
+
+Options:
+  -h, --help             Print this message
+
+Every command but start is sent to the running pincel.
+
+Exit status:
+  0  the command was carried out
+  1  the command failed, or there is no pincel running
+  2  the command line is wrong";
+
+/// What pincel was asked to do
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Opens the overlay
+    Start,
+    /// Sends a request to the running pincel
+    Send(String),
+    Help,
+}
+
 pub struct Cli {
-    pattern: Option<String>,
+    pub action: Action,
+    pub is_frozen: bool,
+    pub is_daemon: bool,
+    pub is_hidden: bool,
+    pub color: Option<String>,
+    pub width: Option<LineWidth>,
+    pub tool: Option<Tool>,
+    pub board: Option<Board>,
+    pub monitor: Option<String>,
+    pub config: Option<PathBuf>,
+    pub screenshot_dir: Option<PathBuf>,
+    pub load: Option<PathBuf>,
 }
 
 impl Cli {
-    pub fn new() -> Self {
-        let pattern = std::env::args().nth(1);
-        Self { pattern }
+    pub fn new() -> Result<Self, String> {
+        Self::parse(env::args().skip(1))
+    }
+
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.peekable();
+        let mut cli = Self {
+            action: Action::Start,
+            is_frozen: false,
+            is_daemon: false,
+            is_hidden: false,
+            color: None,
+            width: None,
+            tool: None,
+            board: None,
+            monitor: None,
+            config: None,
+            screenshot_dir: None,
+            load: None,
+        };
+
+        // with no command, or just flags, pincel starts as it always did
+        let name = args
+            .next_if(|arg| !arg.starts_with('-'))
+            .unwrap_or_else(|| String::from("start"));
+        match name.as_str() {
+            "start" => {}
+            "help" => cli.action = Action::Help,
+            "toggle" | "show" | "clear" | "undo" | "screenshot" | "save" | "quit" => {
+                cli.action = Action::Send(name.clone());
+            }
+            "color" | "width" | "tool" => {
+                let value = args
+                    .next_if(|arg| !arg.starts_with('-'))
+                    .ok_or_else(|| format!("{name} needs a value"))?;
+                cli.action = Action::Send(format!("{name} {value}"));
+            }
+            _ => return Err(format!("unknown command \"{name}\"")),
+        }
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+            match name.as_str() {
+                _ if HELP_FLAGS.contains(&arg.as_str()) => cli.action = Action::Help,
+                "start" => cli.start_flag(&arg, value)?,
+                "screenshot" | "save" if OUTPUT_FLAGS.contains(&arg.as_str()) => {
+                    let path = absolute(value()?)?;
+                    cli.action = Action::Send(format!("{name} {}", path.display()));
+                }
+                _ => return Err(format!("unexpected argument \"{arg}\" for {name}")),
+            }
+        }
+        Ok(cli)
+    }
+
+    /// Whether the drawings are hidden until they are asked for
+    pub fn starts_hidden(&self) -> bool {
+        self.is_daemon || self.is_hidden
+    }
+
+    fn start_flag(
+        &mut self,
+        flag: &str,
+        mut value: impl FnMut() -> Result<String, String>,
+    ) -> Result<(), String> {
+        match flag {
+            FREEZE_FLAG => self.is_frozen = true,
+            DAEMON_FLAG => self.is_daemon = true,
+            HIDDEN_FLAG => self.is_hidden = true,
+            COLOR_FLAG => {
+                let name = value()?;
+                if CurrentColor::from(name.as_str()) == CurrentColor::NotSupported {
+                    return Err(format!("unknown color \"{name}\""));
+                }
+                self.color = Some(name);
+            }
+            WIDTH_FLAG => self.width = Some(LineWidth::try_from(value()?.as_str())?),
+            TOOL_FLAG => self.tool = Some(Tool::try_from(value()?.as_str())?),
+            BACKGROUND_FLAG => self.board = Some(Board::try_from(value()?.as_str())?),
+            MONITOR_FLAG => self.monitor = Some(value()?),
+            CONFIG_FLAG => {
+                // a configuration asked for by name isn't left out quietly
+                let path = absolute(value()?)?;
+                File::open(&path).map_err(|e| format!("can't read {}: {e}", path.display()))?;
+                self.config = Some(path);
+            }
+            SCREENSHOT_DIR_FLAG => self.screenshot_dir = Some(absolute(value()?)?),
+            LOAD_FLAG => self.load = Some(absolute(value()?)?),
+            _ => return Err(format!("unexpected argument \"{flag}\" for start")),
+        }
+        Ok(())
+    }
+}
+
+/// Makes a path given on the command line absolute, as
+/// the running pincel can be in another directory
+fn absolute(path: String) -> Result<PathBuf, String> {
+    let dir = env::current_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Cli, String> {
+        Cli::parse(line.split_whitespace().map(str::to_owned))
+    }
+
+    #[test]
+    fn no_command_starts_pincel() {
+        let cli = parse("").unwrap();
+        assert_eq!(cli.action, Action::Start);
+        assert!(!cli.starts_hidden());
+    }
+
+    #[test]
+    fn commands_are_sent_as_requests() {
+        let commands = [
+            ("toggle", "toggle"),
+            ("show", "show"),
+            ("undo", "undo"),
+            ("color red", "color red"),
+            ("tool eraser", "tool eraser"),
+            ("width thicker", "width thicker"),
+            ("quit", "quit"),
+        ];
+        for (line, request) in commands {
+            let cli = parse(line).unwrap();
+            assert_eq!(cli.action, Action::Send(request.to_owned()), "{line}");
+        }
+    }
+
+    #[test]
+    fn output_paths_are_made_absolute() {
+        let cli = parse("screenshot -o slide.png").unwrap();
+        let path = env::current_dir().unwrap().join("slide.png");
+        assert_eq!(
+            cli.action,
+            Action::Send(format!("screenshot {}", path.display()))
+        );
+    }
+
+    #[test]
+    fn help_wins_over_the_command() {
+        assert_eq!(parse("help").unwrap().action, Action::Help);
+        assert_eq!(parse("undo --help").unwrap().action, Action::Help);
+        assert_eq!(parse("-h").unwrap().action, Action::Help);
+    }
+
+    #[test]
+    fn start_flags_are_read() {
+        let cli = parse("--daemon --color blue --width wide --tool zoom --monitor HDMI-1").unwrap();
+        assert_eq!(cli.action, Action::Start);
+        assert!(cli.starts_hidden());
+        assert_eq!(cli.color.as_deref(), Some("blue"));
+        assert_eq!(cli.width, Some(LineWidth::Wide));
+        assert_eq!(cli.tool, Some(Tool::Zoom));
+        assert_eq!(cli.monitor.as_deref(), Some("HDMI-1"));
+    }
+
+    #[test]
+    fn wrong_command_lines_are_refused() {
+        let errors = [
+            ("redo", "unknown command \"redo\""),
+            ("color", "color needs a value"),
+            ("--color", "--color needs a value"),
+            ("--color pink", "unknown color \"pink\""),
+            ("--fast", "unexpected argument \"--fast\" for start"),
+            ("undo --freeze", "unexpected argument \"--freeze\" for undo"),
+        ];
+        for (line, error) in errors {
+            assert_eq!(parse(line).err().as_deref(), Some(error), "{line}");
+        }
+    }
+
+    #[test]
+    fn a_config_file_that_can_not_be_read_is_refused() {
+        let error = parse("--config /nonexistent/pincel.conf").err().unwrap();
+        assert!(
+            error.starts_with("can't read /nonexistent/pincel.conf"),
+            "{}",
+            error
+        );
     }
 }
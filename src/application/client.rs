@@ -0,0 +1,35 @@
+use super::ipc::Ipc;
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+
+pub const SUCCESS: i32 = 0;
+pub const FAILURE: i32 = 1;
+pub const USAGE_ERROR: i32 = 2;
+
+/// Sends a request to the running pincel and
+/// tells how it went, returning the exit status
+pub fn request(line: &str) -> i32 {
+    match send(line) {
+        Ok(reply) if reply == "ok" => SUCCESS,
+        Ok(reply) => {
+            eprintln!("pincel: {}", reply.trim_start_matches("error: "));
+            FAILURE
+        }
+        Err(e) => {
+            eprintln!("pincel: could not reach a running pincel: {e}");
+            FAILURE
+        }
+    }
+}
+
+fn send(line: &str) -> io::Result<String> {
+    let mut stream = UnixStream::connect(Ipc::path())?;
+    writeln!(stream, "{line}")?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_owned())
+}
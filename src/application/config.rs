@@ -1,18 +1,30 @@
 use std::fs::{self, File};
 use std::io::prelude::*;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{collections::HashMap, env};
 
 const HOME_ENV_VAR: &str = "HOME";
 const CONFIG_DIR: &str = ".config/pincel";
 const CONFIG_FILE_NAME: &str = "pincel.conf";
 const SCREENSHOT_DIR_KEY_CONFIG: &str = "screenshot_dir";
+const LAYERS_KEY_CONFIG: &str = "layers";
+const DEFAULT_LAYERS: &str = "notes,diagram,scratch";
+const MAX_LAYERS: usize = 4;
+const FADE_AFTER_KEY_CONFIG: &str = "fade_after";
+const DEFAULT_FADE_AFTER: u64 = 5;
+const BOARD_COLOR_KEY_CONFIG: &str = "board_color";
+const DEFAULT_BOARD_COLOR: u32 = 0xff1e_2328;
+const BOARD_PATTERN_KEY_CONFIG: &str = "board_pattern";
 
 type Configs = HashMap<String, String>;
 
 pub struct Config {
     pub screenshot_dir: String,
+    pub layers: Vec<String>,
+    pub fade_after: u64,
+    pub board_color: u32,
+    pub board_pattern: String,
     pub configs: Configs,
     config_file_path: PathBuf,
     configs_str: String,
@@ -20,31 +32,55 @@ pub struct Config {
 }
 
 impl Config {
-    pub fn new() -> Self {
+    /// Reads the configuration from the given file, or the usual one
+    pub fn new(file_path: Option<&Path>) -> Self {
         let home_dir = match env::var(HOME_ENV_VAR) {
             Ok(dir) => dir,
             _ => "~/".to_owned(),
         };
+        let mut config = Self::empty(home_dir);
 
+        let contents = match file_path {
+            Some(file_path) => fs::read_to_string(file_path),
+            None => config.read_config_file(),
+        };
+        match contents {
+            Ok(contents) => config.configs_str = contents,
+            Err(e) => println!("Error on reading file: {:?}", e),
+        }
+
+        config.parse_configs();
+        config
+    }
+
+    /// Returns the configuration before any file was read
+    fn empty(home_dir: String) -> Self {
         let home_path = PathBuf::from(home_dir.to_string());
         let config_file_path = home_path.join(PathBuf::from(CONFIG_DIR));
-        let mut config = Self {
+        Self {
             screenshot_dir: String::new(),
+            layers: Vec::new(),
+            fade_after: DEFAULT_FADE_AFTER,
+            board_color: DEFAULT_BOARD_COLOR,
+            board_pattern: String::new(),
             configs: Configs::new(),
             config_file_path,
             configs_str: String::new(),
             home_dir,
-        };
-
-        match config.read_config_file() {
-            Ok(contents) => config.configs_str = contents,
-            Err(e) => println!("Error on reading file: {:?}", e),
         }
+    }
 
-        config.configs = config.extract_configs();
-        config.screenshot_dir = config.screenshot_dir();
-
-        config
+    fn parse_configs(&mut self) {
+        self.configs = self.extract_configs();
+        self.screenshot_dir = self.screenshot_dir();
+        self.layers = self.layers();
+        self.fade_after = self.fade_after();
+        self.board_color = self.board_color();
+        self.board_pattern = self
+            .configs
+            .get(BOARD_PATTERN_KEY_CONFIG)
+            .cloned()
+            .unwrap_or_default();
     }
 
     fn read_config_file(&self) -> std::io::Result<String> {
@@ -76,6 +112,44 @@ impl Config {
         dir
     }
 
+    fn layers(&self) -> Vec<String> {
+        let names = match self.configs.get(LAYERS_KEY_CONFIG) {
+            Some(names) => names.as_str(),
+            None => DEFAULT_LAYERS,
+        };
+
+        // there is one function key to pick each layer
+        let layers = names
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .take(MAX_LAYERS)
+            .map(str::to_owned)
+            .collect::<Vec<String>>();
+
+        if layers.is_empty() {
+            return DEFAULT_LAYERS.split(',').map(str::to_owned).collect();
+        }
+        layers
+    }
+
+    fn fade_after(&self) -> u64 {
+        // seconds the drawings stay on the screen with auto-fade on
+        match self.configs.get(FADE_AFTER_KEY_CONFIG) {
+            Some(seconds) => seconds.parse().unwrap_or(DEFAULT_FADE_AFTER),
+            None => DEFAULT_FADE_AFTER,
+        }
+    }
+
+    fn board_color(&self) -> u32 {
+        // an opaque color written as "#rrggbb"
+        self.configs
+            .get(BOARD_COLOR_KEY_CONFIG)
+            .and_then(|color| color.strip_prefix('#'))
+            .filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .map_or(DEFAULT_BOARD_COLOR, |color| 0xff00_0000 | color)
+    }
+
     fn normalize_relative_path(&self, mut path: String) -> String {
         if path.starts_with("~/") {
             let scr_dir = path.split('~').collect::<Vec<&str>>()[1];
@@ -94,7 +168,15 @@ impl Config {
             }
 
             let line = line.split_whitespace().collect::<String>();
-            let line = line.split('#').collect::<Vec<&str>>()[0]; // ignore end line's comments
+            // ignore end line's comments, but not a '#' in a value like "#rrggbb"
+            let mut is_quoted = false;
+            let end = line
+                .find(|c| {
+                    is_quoted ^= c == '"';
+                    c == '#' && !is_quoted
+                })
+                .unwrap_or(line.len());
+            let line = &line[..end];
             let key_value = line.split('=').collect::<Vec<&str>>();
 
             let key = key_value[0];
@@ -108,3 +190,69 @@ impl Config {
         configs
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(contents: &str) -> Config {
+        let mut config = Config::empty(String::from("/home/me"));
+        config.configs_str = contents.to_owned();
+        config.parse_configs();
+        config
+    }
+
+    #[test]
+    fn board_color_is_read_as_an_opaque_color() {
+        let config = config("board_color = \"#10a0ff\"");
+        assert_eq!(config.board_color, 0xff10_a0ff);
+    }
+
+    #[test]
+    fn comments_are_ignored_after_a_quoted_value() {
+        let config = config("board_color = \"#10a0ff\" # a dark blue");
+        assert_eq!(config.board_color, 0xff10_a0ff);
+    }
+
+    #[test]
+    fn malformed_board_colors_fall_back_to_the_default() {
+        for color in ["#fff", "10a0ff", "#10a0fg", "#10a0ff00", "#+0a0ff"] {
+            let config = config(&format!("board_color = \"{color}\""));
+            assert_eq!(config.board_color, DEFAULT_BOARD_COLOR, "{color}");
+        }
+    }
+
+    #[test]
+    fn layers_are_read_in_order() {
+        let config = config("layers = \"slides, notes\"");
+        assert_eq!(config.layers, ["slides", "notes"]);
+    }
+
+    #[test]
+    fn there_are_at_most_four_layers() {
+        let config = config("layers = \"a, b, c, d, e\"");
+        assert_eq!(config.layers, ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn no_layers_falls_back_to_the_default_ones() {
+        for contents in ["", "layers = \",,\""] {
+            let config = config(contents);
+            assert_eq!(config.layers, ["notes", "diagram", "scratch"], "{contents}");
+        }
+    }
+
+    #[test]
+    fn fade_after_is_read_in_seconds() {
+        assert_eq!(config("fade_after = \"12\"").fade_after, 12);
+        assert_eq!(config("").fade_after, DEFAULT_FADE_AFTER);
+    }
+
+    #[test]
+    fn malformed_fade_after_falls_back_to_the_default() {
+        for seconds in ["-1", "soon", "1.5"] {
+            let config = config(&format!("fade_after = \"{seconds}\""));
+            assert_eq!(config.fade_after, DEFAULT_FADE_AFTER, "{seconds}");
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::iter;
+use std::mem;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+
+const RUNTIME_DIR_ENV_VAR: &str = "XDG_RUNTIME_DIR";
+const SOCKET_NAME: &str = "pincel.sock";
+// a client sending something else than lines is let go
+const MAX_REQUEST_LEN: usize = 4096;
+
+// the keys and buttons the requests stand for
+const COLORS: [(&str, u8); 6] = [
+    ("red", 10),
+    ("blue", 11),
+    ("yellow", 12),
+    ("green", 13),
+    ("orange", 14),
+    ("black", 15),
+];
+const TOOLS: [(&str, u8); 10] = [
+    ("brush", 56),
+    ("eraser", 26),
+    ("select", 39),
+    ("marker", 57),
+    ("rectangle", 27),
+    ("ellipse", 54),
+    ("polygon", 42),
+    ("laser", 46),
+    ("spotlight", 43),
+    ("zoom", 52),
+];
+const SCREENSHOT_KEY: u8 = 33;
+const TOGGLE_KEY: u8 = 75;
+const CLEAR_BUTTON: u8 = 2;
+const UNDO_BUTTON: u8 = 3;
+const THICKER_BUTTON: u8 = 4;
+const THINNER_BUTTON: u8 = 5;
+
+/// What a request asks pincel to do, as the input it stands for
+#[derive(Clone, Debug, PartialEq)]
+pub enum Request {
+    Key(u8),
+    /// A key only pressed while the drawings are hidden
+    Show(u8),
+    Button(u8),
+    /// Saves a screenshot to the given path
+    Screenshot(String),
+    /// Saves the drawings, to the given path or the usual one
+    Save(Option<String>),
+    Quit,
+}
+
+impl Request {
+    /// Parses a request line like "tool eraser" or "undo"
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        // the argument is the rest of the line, as paths can have spaces
+        let (name, arg) = line
+            .split_once(' ')
+            .map_or((line, ""), |(name, arg)| (name, arg.trim()));
+        let find = |table: &[(&str, u8)]| {
+            table
+                .iter()
+                .find(|(key, _)| *key == arg)
+                .map(|&(_, code)| code)
+                .ok_or_else(|| format!("unknown {name} \"{arg}\""))
+        };
+
+        match name {
+            "toggle" => Ok(Self::Key(TOGGLE_KEY)),
+            "show" => Ok(Self::Show(TOGGLE_KEY)),
+            "clear" => Ok(Self::Button(CLEAR_BUTTON)),
+            "undo" => Ok(Self::Button(UNDO_BUTTON)),
+            "screenshot" if arg.is_empty() => Ok(Self::Key(SCREENSHOT_KEY)),
+            "screenshot" => Ok(Self::Screenshot(arg.to_owned())),
+            "quit" => Ok(Self::Quit),
+            "save" => Ok(Self::Save(
+                Some(arg.to_owned()).filter(|path| !path.is_empty()),
+            )),
+            "color" => find(&COLORS).map(Self::Key),
+            "tool" => find(&TOOLS).map(Self::Key),
+            "width" => match arg {
+                "thicker" => Ok(Self::Button(THICKER_BUTTON)),
+                "thinner" => Ok(Self::Button(THINNER_BUTTON)),
+                _ => Err(format!("unknown width \"{arg}\"")),
+            },
+            _ => Err(format!("unknown request \"{name}\"")),
+        }
+    }
+}
+
+/// A Unix socket taking one request per line, answering
+/// each with "ok" or "error: " and what went wrong
+pub struct Ipc {
+    listener: UnixListener,
+    path: PathBuf,
+    clients: Vec<Client>,
+}
+
+/// A connection to the socket, with what was sent
+/// through it that isn't a whole line yet
+struct Client {
+    stream: UnixStream,
+    pending: Vec<u8>,
+}
+
+impl Ipc {
+    pub fn bind() -> io::Result<Self> {
+        let path = Self::path();
+        // a socket left behind by a pincel that crashed
+        if path.exists() && UnixStream::connect(&path).is_err() {
+            fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            path,
+            clients: Vec::new(),
+        })
+    }
+
+    pub fn path() -> PathBuf {
+        runtime_file(SOCKET_NAME)
+    }
+
+    /// Returns the socket and the connections to it, to wait for
+    pub fn fds(&self) -> Vec<RawFd> {
+        iter::once(self.listener.as_raw_fd())
+            .chain(self.clients.iter().map(|client| client.stream.as_raw_fd()))
+            .collect()
+    }
+
+    /// Takes the clients waiting to connect and answers the requests
+    /// sent so far, never waiting for a client that went quiet
+    pub fn serve(&mut self, mut handle: impl FnMut(&str) -> Result<(), String>) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    self.clients.push(Client {
+                        stream,
+                        pending: Vec::new(),
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.clients = mem::take(&mut self.clients)
+            .into_iter()
+            .filter_map(|mut client| client.serve(&mut handle).then(|| client))
+            .collect();
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Reads what was sent once, as the socket is waited for again
+    /// if there is more, and answers the whole lines so far,
+    /// returning whether the client is still connected
+    fn serve(&mut self, handle: &mut impl FnMut(&str) -> Result<(), String>) -> bool {
+        let mut buf = [0; 1024];
+        let is_open = match self.stream.read(&mut buf) {
+            Ok(0) => false,
+            Ok(len) => {
+                self.pending.extend(&buf[..len]);
+                true
+            }
+            Err(e) => matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+            ),
+        };
+        // the last line needs no end once the client is done sending
+        if !is_open && !self.pending.is_empty() {
+            self.pending.push(b'\n');
+        }
+
+        while let Some(end) = self.pending.iter().position(|&byte| byte == b'\n') {
+            let line = self.pending.drain(..=end).collect::<Vec<u8>>();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match handle(&line) {
+                Ok(()) => String::from("ok"),
+                Err(e) => format!("error: {e}"),
+            };
+            if writeln!(self.stream, "{reply}").is_err() {
+                return false;
+            }
+        }
+        is_open && self.pending.len() <= MAX_REQUEST_LEN
+    }
+}
+
+/// Returns where a file only meant for this session goes
+pub fn runtime_file(name: &str) -> PathBuf {
+    if let Ok(dir) = env::var(RUNTIME_DIR_ENV_VAR) {
+        return PathBuf::from(dir).join(name);
+    }
+
+    // the temporary directory is shared with the other users
+    let uid = unsafe { libc::getuid() };
+    let name = match name.split_once('.') {
+        Some((stem, extension)) => format!("{stem}-{uid}.{extension}"),
+        None => format!("{name}-{uid}"),
+    };
+    env::temp_dir().join(name)
+}
+
+impl Drop for Ipc {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn requests_are_parsed_to_their_keys_and_buttons() {
+        let requests = [
+            ("toggle", Request::Key(TOGGLE_KEY)),
+            ("show", Request::Show(TOGGLE_KEY)),
+            ("clear", Request::Button(CLEAR_BUTTON)),
+            ("undo\n", Request::Button(UNDO_BUTTON)),
+            ("color orange", Request::Key(14)),
+            ("tool  eraser ", Request::Key(26)),
+            ("width thinner", Request::Button(THINNER_BUTTON)),
+            ("screenshot", Request::Key(SCREENSHOT_KEY)),
+            ("quit", Request::Quit),
+        ];
+        for (line, request) in requests {
+            assert_eq!(Request::parse(line), Ok(request), "{line}");
+        }
+    }
+
+    #[test]
+    fn paths_are_the_rest_of_the_line() {
+        assert_eq!(
+            Request::parse("screenshot /tmp/my slide.png"),
+            Ok(Request::Screenshot(String::from("/tmp/my slide.png")))
+        );
+        assert_eq!(
+            Request::parse("save /tmp/talk.json"),
+            Ok(Request::Save(Some(String::from("/tmp/talk.json"))))
+        );
+        assert_eq!(Request::parse("save"), Ok(Request::Save(None)));
+    }
+
+    #[test]
+    fn unknown_requests_are_told_apart() {
+        let errors = [
+            ("color pink", "unknown color \"pink\""),
+            ("tool", "unknown tool \"\""),
+            ("width wide", "unknown width \"wide\""),
+            ("redo", "unknown request \"redo\""),
+        ];
+        for (line, error) in errors {
+            assert_eq!(Request::parse(line), Err(String::from(error)), "{line}");
+        }
+    }
+
+    #[test]
+    fn a_quiet_client_is_not_waited_for() {
+        let (stream, mut other) = UnixStream::pair().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut client = Client {
+            stream,
+            pending: Vec::new(),
+        };
+        let mut requests = Vec::new();
+        let mut handle = |line: &str| {
+            requests.push(line.trim().to_owned());
+            Ok(())
+        };
+
+        write!(other, "undo\ncle").unwrap();
+        assert!(client.serve(&mut handle));
+        assert!(client.serve(&mut handle));
+        writeln!(other, "ar").unwrap();
+        assert!(client.serve(&mut handle));
+        assert_eq!(requests, ["undo", "clear"]);
+
+        let mut replies = BufReader::new(other).lines();
+        assert_eq!(replies.next().unwrap().unwrap(), "ok");
+        assert_eq!(replies.next().unwrap().unwrap(), "ok");
+    }
+}
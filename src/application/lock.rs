@@ -0,0 +1,47 @@
+use super::ipc::runtime_file;
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+
+use libc::{EWOULDBLOCK, LOCK_EX, LOCK_NB, O_NOFOLLOW, O_NONBLOCK};
+
+const LOCK_NAME: &str = "pincel.lock";
+
+/// Held by the one pincel drawing on the screen, and
+/// let go by the system when it exits, even if it crashes
+pub struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    /// Takes the lock, or returns nothing when another pincel has it
+    pub fn acquire() -> io::Result<Option<Self>> {
+        let path = runtime_file(LOCK_NAME);
+        // the path can be in the shared temporary directory, so a link, a
+        // pipe or a file of another user put there is refused, not written
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .mode(0o600)
+            .custom_flags(O_NOFOLLOW | O_NONBLOCK)
+            .open(&path)?;
+        let metadata = file.metadata()?;
+        if !metadata.is_file() || metadata.uid() != unsafe { libc::getuid() } {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} isn't a file of this user", path.display()),
+            ));
+        }
+
+        if unsafe { libc::flock(file.as_raw_fd(), LOCK_EX | LOCK_NB) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(EWOULDBLOCK) {
+                return Ok(None);
+            }
+            return Err(err);
+        }
+        Ok(Some(Self { _file: file }))
+    }
+}
@@ -1,7 +1,11 @@
 pub mod app;
 pub mod app_initializer;
 pub mod cli;
+pub mod client;
 pub mod config;
+pub mod ipc;
+pub mod lock;
+pub mod poller;
 
 pub use app::*;
 pub use config::*;
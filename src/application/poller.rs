@@ -0,0 +1,39 @@
+use std::convert::TryFrom;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+use libc::{nfds_t, pollfd, POLLIN};
+
+/// Waits until any of the file descriptors can be read or the timeout
+/// passes, blocking for good without a timeout, and returns the readable ones
+pub fn wait(fds: &[RawFd], timeout: Option<Duration>) -> io::Result<Vec<RawFd>> {
+    let mut poll_fds = fds
+        .iter()
+        .map(|&fd| pollfd {
+            fd,
+            events: POLLIN,
+            revents: 0,
+        })
+        .collect::<Vec<pollfd>>();
+
+    // round up, so a timer isn't woken up before it's due
+    let timeout = timeout.map_or(-1, |timeout| {
+        i32::try_from((timeout.as_micros() + 999) / 1000).unwrap_or(i32::MAX)
+    });
+
+    let len = nfds_t::try_from(poll_fds.len()).unwrap_or(nfds_t::MAX);
+    if unsafe { libc::poll(poll_fds.as_mut_ptr(), len, timeout) } < 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() == io::ErrorKind::Interrupted {
+            return Ok(Vec::new());
+        }
+        return Err(err);
+    }
+
+    Ok(poll_fds
+        .iter()
+        .filter(|poll_fd| poll_fd.revents != 0)
+        .map(|poll_fd| poll_fd.fd)
+        .collect())
+}
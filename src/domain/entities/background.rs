@@ -0,0 +1,141 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ChangeGCAux, ConnectionExt, LineStyle, Rectangle, Segment};
+
+use std::convert::TryFrom;
+use std::error::Error;
+
+const WHITE_BOARD_COLOR: u32 = 0xffff_ffff;
+const WHITE_BOARD_LINE_COLOR: u32 = 0xffc8_d2dc;
+const DARK_BOARD_LINE_COLOR: u32 = 0xff50_5a64;
+const PATTERN_SPACING: usize = 32;
+
+/// What the drawings are drawn over
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Board {
+    /// The screen behind the window
+    Transparent,
+    White,
+    Dark,
+}
+
+impl TryFrom<&str> for Board {
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "transparent" => Ok(Self::Transparent),
+            "white" => Ok(Self::White),
+            "dark" => Ok(Self::Dark),
+            _ => Err(format!("unknown background \"{name}\"")),
+        }
+    }
+}
+
+/// The lines drawn over an opaque board
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pattern {
+    Plain,
+    Grid,
+    Lined,
+}
+
+impl From<&str> for Pattern {
+    fn from(name: &str) -> Self {
+        match name {
+            "grid" => Self::Grid,
+            "lined" => Self::Lined,
+            _ => Self::Plain,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Background {
+    pub board: Board,
+    pub pattern: Pattern,
+    dark_color: u32,
+}
+
+impl Background {
+    pub fn new(dark_color: u32, pattern: Pattern) -> Self {
+        Self {
+            board: Board::Transparent,
+            pattern,
+            dark_color,
+        }
+    }
+
+    pub fn next_board(&mut self) {
+        self.board = match self.board {
+            Board::Transparent => Board::White,
+            Board::White => Board::Dark,
+            Board::Dark => Board::Transparent,
+        };
+    }
+
+    pub fn next_pattern(&mut self) {
+        self.pattern = match self.pattern {
+            Pattern::Plain => Pattern::Grid,
+            Pattern::Grid => Pattern::Lined,
+            Pattern::Lined => Pattern::Plain,
+        };
+    }
+
+    pub fn expose(
+        &self,
+        conn: &impl Connection,
+        win: u32,
+        gc: u32,
+        (width, height): (i16, i16),
+    ) -> Result<(), Box<dyn Error>> {
+        let (color, line_color) = match self.board {
+            Board::Transparent => return Ok(()),
+            Board::White => (WHITE_BOARD_COLOR, WHITE_BOARD_LINE_COLOR),
+            Board::Dark => (self.dark_color, DARK_BOARD_LINE_COLOR),
+        };
+
+        conn.change_gc(gc, &ChangeGCAux::new().foreground(color))?;
+        conn.poly_fill_rectangle(
+            win,
+            gc,
+            &[Rectangle {
+                x: 0,
+                y: 0,
+                width: width.unsigned_abs(),
+                height: height.unsigned_abs(),
+            }],
+        )?;
+
+        let mut segments = Vec::new();
+        if self.pattern != Pattern::Plain {
+            for y in (0..height).step_by(PATTERN_SPACING) {
+                segments.push(Segment {
+                    x1: 0,
+                    y1: y,
+                    x2: width,
+                    y2: y,
+                });
+            }
+        }
+        if self.pattern == Pattern::Grid {
+            for x in (0..width).step_by(PATTERN_SPACING) {
+                segments.push(Segment {
+                    x1: x,
+                    y1: 0,
+                    x2: x,
+                    y2: height,
+                });
+            }
+        }
+
+        conn.change_gc(
+            gc,
+            &ChangeGCAux::new()
+                .foreground(line_color)
+                .line_width(1)
+                .line_style(LineStyle::Solid),
+        )?;
+        conn.poly_segment(win, gc, &segments)?;
+        Ok(())
+    }
+}
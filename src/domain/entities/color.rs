@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex, Once};
 
 #[derive(Clone, Debug)]
@@ -33,7 +34,8 @@ impl CurrentColorSingleton {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CurrentColor {
     Red,
     Blue,
@@ -58,6 +60,51 @@ impl CurrentColor {
     }
 }
 
+/// Returns the pixel value of an opaque color with the given
+/// opacity, in percent, premultiplied as the compositor expects it
+pub fn premultiply(color: u32, opacity: u8) -> u32 {
+    let alpha = u32::from(opacity) * 0xff / 100;
+    let channel = |shift: u32| (((color >> shift) & 0xff) * alpha / 0xff) << shift;
+    (alpha << 24) | channel(16) | channel(8) | channel(0)
+}
+
+pub const FILL_OPACITIES: [u8; 4] = [0, 25, 50, 100];
+
+/// The color and opacity, in percent, the inside
+/// of closed shapes is painted with
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub color: CurrentColor,
+    pub opacity: u8,
+}
+
+impl Fill {
+    pub fn new() -> Self {
+        Self {
+            color: CurrentColor::Yellow,
+            opacity: 0,
+        }
+    }
+
+    /// Returns the fill, unless it is fully transparent
+    pub fn visible(self) -> Option<Self> {
+        Some(self).filter(|fill| fill.opacity > 0)
+    }
+
+    /// Returns the fill with the next opacity, going
+    /// back to no fill after the fully opaque one
+    pub fn next_opacity(self) -> Self {
+        let next = FILL_OPACITIES
+            .iter()
+            .position(|&opacity| opacity == self.opacity)
+            .map_or(0, |index| (index + 1) % FILL_OPACITIES.len());
+        Self {
+            opacity: FILL_OPACITIES[next],
+            ..self
+        }
+    }
+}
+
 impl From<&str> for CurrentColor {
     fn from(name: &str) -> Self {
         match name {
@@ -78,3 +125,43 @@ impl From<CurrentColorSingleton> for CurrentColor {
         *data
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_opacities_cycle_back_to_no_fill() {
+        let mut fill = Fill::new();
+        let mut opacities = Vec::new();
+        for _ in 0..5 {
+            fill = fill.next_opacity();
+            opacities.push(fill.opacity);
+        }
+        assert_eq!(opacities, [25, 50, 100, 0, 25]);
+        assert_eq!(fill.color, CurrentColor::Yellow);
+    }
+
+    #[test]
+    fn unknown_opacities_start_over() {
+        let fill = Fill {
+            opacity: 30,
+            ..Fill::new()
+        };
+        assert_eq!(fill.next_opacity().opacity, 0);
+    }
+
+    #[test]
+    fn only_some_fills_are_visible() {
+        assert_eq!(Fill::new().visible(), None);
+        let fill = Fill::new().next_opacity();
+        assert_eq!(fill.visible(), Some(fill));
+    }
+
+    #[test]
+    fn colors_are_premultiplied_by_their_opacity() {
+        assert_eq!(premultiply(0xff20_4080, 100), 0xff20_4080);
+        assert_eq!(premultiply(0xff20_4080, 50), 0x7f0f_1f3f);
+        assert_eq!(premultiply(0xff20_4080, 0), 0);
+    }
+}
@@ -0,0 +1,86 @@
+use std::convert::TryFrom;
+
+use super::layer::Layer;
+use super::movement::Movement;
+
+const ERASER_RADIUS: i16 = 10;
+
+/// A brush that removes only the parts of the movements
+/// it passes over, splitting them into the remaining pieces
+#[derive(Clone, Debug)]
+pub struct Eraser {
+    radius: i16,
+    last: Option<(i16, i16)>,
+}
+
+impl Eraser {
+    pub fn new() -> Self {
+        Self {
+            radius: ERASER_RADIUS,
+            last: None,
+        }
+    }
+
+    /// Erases the visible movements along the path from the last
+    /// position to `(x, y)`, returning whether any movement was changed
+    pub fn erase(
+        &mut self,
+        stack: &mut Vec<Option<Movement>>,
+        layers: &[Layer],
+        (x, y): (i16, i16),
+    ) -> bool {
+        let (last_x, last_y) = self.last.unwrap_or((x, y));
+        self.last = Some((x, y));
+
+        // Sample the path between two motion events, so a fast
+        // drag doesn't jump over the lines it crosses
+        let distance = i32::max(
+            (i32::from(x) - i32::from(last_x)).abs(),
+            (i32::from(y) - i32::from(last_y)).abs(),
+        );
+        let steps = distance / i32::from(self.radius / 2).max(1) + 1;
+
+        let mut changed = false;
+        for i in 0..=steps {
+            let point = (lerp(last_x, x, i, steps), lerp(last_y, y, i, steps));
+            changed |= self.erase_at(stack, layers, point);
+        }
+        changed
+    }
+
+    pub fn finish(&mut self) {
+        self.last = None;
+    }
+
+    fn erase_at(
+        &self,
+        stack: &mut Vec<Option<Movement>>,
+        layers: &[Layer],
+        point: (i16, i16),
+    ) -> bool {
+        let mut changed = false;
+        let mut remaining = Vec::with_capacity(stack.len());
+
+        for mov in stack.drain(..) {
+            match mov
+                .as_ref()
+                .filter(|m| Layer::shows(layers, m))
+                .and_then(|m| m.erase(point, self.radius))
+            {
+                Some(pieces) => {
+                    changed = true;
+                    remaining.extend(pieces.into_iter().map(Some));
+                }
+                None => remaining.push(mov),
+            }
+        }
+
+        *stack = remaining;
+        changed
+    }
+}
+
+fn lerp(from: i16, to: i16, step: i32, steps: i32) -> i16 {
+    let value = i32::from(from) + (i32::from(to) - i32::from(from)) * step / steps;
+    i16::try_from(value).unwrap_or(to)
+}
@@ -1,26 +1,171 @@
+use super::color::premultiply;
 use crate::domain::error::PincelError;
+use serde::{Deserialize, Serialize};
 use x11rb::{
     connection::Connection,
     protocol::xproto::{
-        create_colormap, ChangeGCAux, ColormapAlloc, CreateGCAux, Depth, Screen, Visualtype,
+        create_colormap, ChangeGCAux, ChangeWindowAttributesAux, ColormapAlloc, ConnectionExt,
+        CoordMode, CreateGCAux, Depth, FillStyle, ImageFormat, ImageOrder, LineStyle, Point,
+        Rectangle, Screen, VisualClass, Visualtype,
     },
+    NONE,
 };
 
+use std::convert::TryFrom;
+
+const SELECTION_COLOR: u32 = 0xff1e_90ff;
+const PUT_IMAGE_HEADER: usize = 24;
+// the first font the server has is used, "fixed" is always there
+const FONTS: [&str; 3] = [
+    "-*-helvetica-bold-r-normal--18-*-*-*-*-*-iso8859-1",
+    "9x15bold",
+    "fixed",
+];
+// a 4x4 ordered dither, each cell has a dot from its level up
+const DITHER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+const DITHER_LEVELS: u8 = 16;
+const DITHER_SIZE: u16 = 4;
+
 pub struct GraphicContext;
 
-#[derive(Clone, Copy)]
+/// How the server lays the pixels of the images of a depth out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelFormat {
+    /// The bytes each pixel takes
+    pub bytes: usize,
+    /// The bytes each row is padded to a multiple of
+    pad: usize,
+    /// Where the alpha is in a pixel of 32 bits
+    alpha: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LineWidth {
     Thin = 1,
     Normal = 2,
     Wide = 3,
 }
 
+impl LineWidth {
+    pub fn thicker(self) -> Self {
+        match self {
+            LineWidth::Thin => LineWidth::Normal,
+            LineWidth::Normal | LineWidth::Wide => LineWidth::Wide,
+        }
+    }
+
+    pub fn thinner(self) -> Self {
+        match self {
+            LineWidth::Thin | LineWidth::Normal => LineWidth::Thin,
+            LineWidth::Wide => LineWidth::Normal,
+        }
+    }
+}
+
+impl TryFrom<&str> for LineWidth {
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "thin" => Ok(Self::Thin),
+            "normal" => Ok(Self::Normal),
+            "wide" => Ok(Self::Wide),
+            _ => Err(format!("unknown width \"{name}\"")),
+        }
+    }
+}
+
+/// How translucent colors are painted: with their alpha, for the
+/// compositor to blend, or as a pattern of opaque dots, as nothing
+/// blends them over a still copy of the screen
+#[derive(Clone, Debug)]
+pub enum Blending {
+    Alpha,
+    /// The stipples for each level of opacity, in sixteenths
+    Dither(Vec<u32>),
+}
+
+impl Blending {
+    pub fn dither(conn: &(impl Connection + Send + Sync), win: u32) -> Result<Self, PincelError> {
+        let gc = conn.generate_id()?;
+        let mut stipples = Vec::with_capacity(usize::from(DITHER_LEVELS));
+        for level in 0..DITHER_LEVELS {
+            let stipple = conn.generate_id()?;
+            conn.create_pixmap(1, stipple, win, DITHER_SIZE, DITHER_SIZE)?
+                .check()?;
+            if stipples.is_empty() {
+                conn.create_gc(gc, stipple, &CreateGCAux::new())?;
+            }
+
+            let dots = DITHER
+                .iter()
+                .zip(0..)
+                .flat_map(|(row, y)| row.iter().zip(0..).map(move |(&cell, x)| (cell, x, y)))
+                .filter(|&(cell, _, _)| cell < level)
+                .map(|(_, x, y)| Point { x, y })
+                .collect::<Vec<Point>>();
+            conn.change_gc(gc, &ChangeGCAux::new().foreground(0))?;
+            conn.poly_fill_rectangle(
+                stipple,
+                gc,
+                &[Rectangle {
+                    x: 0,
+                    y: 0,
+                    width: DITHER_SIZE,
+                    height: DITHER_SIZE,
+                }],
+            )?;
+            conn.change_gc(gc, &ChangeGCAux::new().foreground(1))?;
+            conn.poly_point(CoordMode::Origin, stipple, gc, &dots)?;
+            stipples.push(stipple);
+        }
+        conn.free_gc(gc)?;
+        Ok(Self::Dither(stipples))
+    }
+
+    /// Returns how to paint an opaque color with the given opacity, in percent
+    pub fn color(&self, color: u32, opacity: u8) -> ChangeGCAux {
+        let level = (u32::from(opacity) * u32::from(DITHER_LEVELS) + 50) / 100;
+        match self {
+            Self::Dither(stipples) => match stipples.get(level as usize) {
+                Some(&stipple) => ChangeGCAux::new()
+                    .foreground(color)
+                    .fill_style(FillStyle::Stippled)
+                    .stipple(stipple),
+                None => Self::solid(color),
+            },
+            Self::Alpha => Self::solid(premultiply(color, opacity)),
+        }
+    }
+
+    /// Returns how to paint an opaque color, after a translucent one
+    pub fn solid(color: u32) -> ChangeGCAux {
+        ChangeGCAux::new()
+            .foreground(color)
+            .fill_style(FillStyle::Solid)
+    }
+}
+
 impl GraphicContext {
-    pub fn create_gc(color: u32, line_width: LineWidth) -> CreateGCAux {
+    pub fn create_gc(color: u32, line_width: LineWidth, font: u32) -> CreateGCAux {
         CreateGCAux::new()
             .foreground(color)
             .graphics_exposures(1)
             .line_width(line_width as u32)
+            .font(font)
+    }
+
+    pub fn open_font(conn: &(impl Connection + Send + Sync)) -> Result<u32, PincelError> {
+        let font = conn.generate_id()?;
+        let (fallback, fonts) = FONTS.split_last().unwrap();
+        for name in fonts {
+            if conn.open_font(font, name.as_bytes())?.check().is_ok() {
+                return Ok(font);
+            }
+        }
+        conn.open_font(font, fallback.as_bytes())?.check()?;
+        Ok(font)
     }
 
     pub fn change_color(color: u32, line_width: LineWidth) -> ChangeGCAux {
@@ -28,17 +173,49 @@ impl GraphicContext {
             .foreground(color)
             .graphics_exposures(1)
             .line_width(line_width as u32)
+            .line_style(LineStyle::Solid)
     }
 
-    pub fn fetch_visual_alpha(depths: &[Depth]) -> Result<Visualtype, PincelError> {
-        let mut alpha_depths = depths
+    pub fn selection() -> ChangeGCAux {
+        ChangeGCAux::new()
+            .foreground(SELECTION_COLOR)
+            .line_width(1)
+            .line_style(LineStyle::OnOffDash)
+    }
+
+    /// Returns a true color visual with bits left for an alpha channel
+    pub fn fetch_visual_alpha(depths: &[Depth]) -> Option<Visualtype> {
+        depths
             .iter()
             .filter(|d: &&Depth| d.depth == 32_u8)
-            .peekable();
-        if alpha_depths.peek().is_none() {
-            panic!("Alpha channel not found!");
-        }
-        return Ok(*alpha_depths.next().unwrap().visuals.get(1).unwrap());
+            .flat_map(|d| &d.visuals)
+            .find(|v| {
+                v.class == VisualClass::TrueColor
+                    && v.red_mask | v.green_mask | v.blue_mask != u32::MAX
+            })
+            .copied()
+    }
+
+    /// Returns the visual of the root window, which can't be seen through
+    pub fn fetch_visual_root(screen: &Screen) -> Result<Visualtype, PincelError> {
+        screen
+            .allowed_depths
+            .iter()
+            .flat_map(|d| &d.visuals)
+            .find(|v| v.visual_id == screen.root_visual)
+            .copied()
+            .ok_or(PincelError::MissingWinParams)
+    }
+
+    /// Checks if a compositing manager runs on the screen, so
+    /// the transparent parts of the window show what's behind
+    pub fn is_composited(
+        conn: &(impl Connection + Send + Sync),
+        screen_num: usize,
+    ) -> Result<bool, PincelError> {
+        let name = format!("_NET_WM_CM_S{screen_num}");
+        let atom = conn.intern_atom(false, name.as_bytes())?.reply()?.atom;
+        Ok(conn.get_selection_owner(atom)?.reply()?.owner != NONE)
     }
 
     pub fn genarate_colormap(
@@ -56,4 +233,158 @@ impl GraphicContext {
         )?;
         Ok(colormap)
     }
+
+    /// Copies the area of the screen under the window into a pixmap
+    /// for it, made opaque so it can be drawn over without a compositor
+    pub fn snapshot(
+        conn: &(impl Connection + Send + Sync),
+        (root, area): (u32, Rectangle),
+        (win, depth): (u32, u8),
+        gc: u32,
+    ) -> Result<u32, PincelError> {
+        let (width, height) = (area.width, area.height);
+        let format = PixelFormat::of(conn, depth)?;
+        let image = conn
+            .get_image(
+                ImageFormat::ZPixmap,
+                root,
+                area.x,
+                area.y,
+                width,
+                height,
+                !0,
+            )?
+            .reply()?;
+        // the pixels are copied as they are, so the
+        // screen has to lay them out like the window
+        if PixelFormat::of(conn, image.depth)? != format {
+            return Err(PincelError::UnsupportedDepth(image.depth));
+        }
+        let mut data = image.data;
+        let row = format.row(usize::from(width));
+        for line in data.chunks_mut(row) {
+            for pixel in line.chunks_mut(format.bytes).take(usize::from(width)) {
+                format.make_opaque(pixel);
+            }
+        }
+
+        let pixmap = conn.generate_id()?;
+        // the errors are waited for, not to arrive later and end pincel
+        conn.create_pixmap(depth, pixmap, win, width, height)?
+            .check()?;
+
+        // the image is sent in strips that fit in a request
+        let rows = usize::max((conn.maximum_request_bytes() - PUT_IMAGE_HEADER) / row, 1);
+        for (index, strip) in data.chunks(rows * row).enumerate() {
+            let put = conn.put_image(
+                ImageFormat::ZPixmap,
+                pixmap,
+                gc,
+                width,
+                u16::try_from(strip.len() / row).unwrap_or(height),
+                0,
+                i16::try_from(index * rows).unwrap_or(i16::MAX),
+                0,
+                depth,
+                strip,
+            )?;
+            if let Err(e) = put.check() {
+                conn.free_pixmap(pixmap)?;
+                return Err(e.into());
+            }
+        }
+        Ok(pixmap)
+    }
+
+    /// Shows a still copy of the screen behind the drawings
+    pub fn freeze(
+        conn: &(impl Connection + Send + Sync),
+        (root, area): (u32, Rectangle),
+        (win, depth): (u32, u8),
+        gc: u32,
+    ) -> Result<(), PincelError> {
+        let snapshot = Self::snapshot(conn, (root, area), (win, depth), gc)?;
+        let shown = conn
+            .change_window_attributes(
+                win,
+                &ChangeWindowAttributesAux::new().background_pixmap(snapshot),
+            )?
+            .check();
+        conn.free_pixmap(snapshot)?;
+        shown?;
+        Ok(())
+    }
+}
+
+impl PixelFormat {
+    /// Looks the format of `depth` up, refusing
+    /// the ones packing many pixels in a byte
+    pub fn of(conn: &impl Connection, depth: u8) -> Result<Self, PincelError> {
+        let setup = conn.setup();
+        let format = setup
+            .pixmap_formats
+            .iter()
+            .find(|format| format.depth == depth && format.bits_per_pixel % 8 == 0)
+            .ok_or(PincelError::UnsupportedDepth(depth))?;
+        let bytes = usize::from(format.bits_per_pixel / 8);
+        let alpha = match setup.image_byte_order {
+            ImageOrder::LSBFirst => 3,
+            ImageOrder::MSBFirst => 0,
+        };
+        Ok(Self {
+            bytes,
+            pad: usize::max(usize::from(format.scanline_pad / 8), 1),
+            alpha: Some(alpha).filter(|_| bytes == 4),
+        })
+    }
+
+    /// Returns the bytes a row of `width` pixels takes, with its padding
+    pub fn row(self, width: usize) -> usize {
+        let bytes = width * self.bytes;
+        bytes + (self.pad - bytes % self.pad) % self.pad
+    }
+
+    /// Makes a pixel opaque for the overlay, when it has an alpha
+    pub fn make_opaque(self, pixel: &mut [u8]) {
+        if let Some(alpha) = self.alpha {
+            pixel[alpha] = 0xff;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_padded_to_the_scanline_pad() {
+        let format = PixelFormat {
+            bytes: 2,
+            pad: 4,
+            alpha: None,
+        };
+        assert_eq!(format.row(3), 8);
+        assert_eq!(format.row(4), 8);
+    }
+
+    #[test]
+    fn only_pixels_of_32_bits_are_made_opaque() {
+        let mut pixel = [1, 2, 3, 0];
+        PixelFormat {
+            bytes: 4,
+            pad: 4,
+            alpha: Some(3),
+        }
+        .make_opaque(&mut pixel);
+        assert_eq!(pixel, [1, 2, 3, 0xff]);
+
+        let mut pixel = [1, 2, 3];
+        PixelFormat {
+            bytes: 3,
+            pad: 4,
+            alpha: None,
+        }
+        .make_opaque(&mut pixel);
+        assert_eq!(pixel, [1, 2, 3]);
+    }
 }
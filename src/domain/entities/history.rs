@@ -0,0 +1,103 @@
+use super::movement::Movement;
+
+const HISTORY_LIMIT: usize = 100;
+
+/// Snapshots of the stack taken before every change,
+/// so each change can be undone
+#[derive(Clone, Debug)]
+pub struct History {
+    snapshots: Vec<Vec<Option<Movement>>>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// Saves the stack as it is before a change
+    pub fn record(&mut self, stack: &[Option<Movement>]) {
+        if self.snapshots.len() == HISTORY_LIMIT {
+            self.snapshots.remove(0);
+        }
+        self.snapshots.push(stack.to_vec());
+    }
+
+    /// Drops the last snapshot if the change it was
+    /// recorded for didn't end up changing anything
+    pub fn discard_unchanged(&mut self, stack: &[Option<Movement>]) {
+        if self.snapshots.last().map(Vec::as_slice) == Some(stack) {
+            self.snapshots.pop();
+        }
+    }
+
+    /// Restores the stack as it was before the last change,
+    /// returning false if there is nothing to undo
+    pub fn undo(&mut self, stack: &mut Vec<Option<Movement>>) -> bool {
+        match self.snapshots.pop() {
+            Some(snapshot) => {
+                *stack = snapshot;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{CurrentColor, LineWidth};
+
+    fn movement(x: i16) -> Movement {
+        Movement::from_lines(vec![(x, 0), (x, 10)], CurrentColor::Red, LineWidth::Thin)
+    }
+
+    #[test]
+    fn undo_restores_the_stack_before_each_change() {
+        let mut history = History::new();
+        let mut stack = Vec::new();
+        history.record(&stack);
+        stack.push(Some(movement(0)));
+        history.record(&stack);
+        stack.push(Some(movement(1)));
+
+        assert!(history.undo(&mut stack));
+        assert_eq!(stack, [Some(movement(0))]);
+        assert!(history.undo(&mut stack));
+        assert!(stack.is_empty());
+        assert!(!history.undo(&mut stack));
+    }
+
+    #[test]
+    fn changes_that_changed_nothing_are_not_undone() {
+        let mut history = History::new();
+        let mut stack = vec![Some(movement(0))];
+        history.record(&stack);
+        stack.push(Some(movement(1)));
+        history.record(&stack);
+        history.discard_unchanged(&stack);
+
+        assert!(history.undo(&mut stack));
+        assert_eq!(stack, [Some(movement(0))]);
+        assert!(!history.undo(&mut stack));
+    }
+
+    #[test]
+    fn only_the_latest_changes_are_kept() {
+        let mut history = History::new();
+        let mut stack = Vec::new();
+        for x in 0..=100 {
+            history.record(&stack);
+            stack.push(Some(movement(x)));
+        }
+
+        let mut undone = 0;
+        while history.undo(&mut stack) {
+            undone += 1;
+        }
+        assert_eq!(undone, HISTORY_LIMIT);
+        assert_eq!(stack, [Some(movement(0))]);
+    }
+}
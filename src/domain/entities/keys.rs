@@ -1,15 +1,44 @@
 #[derive(Debug, Copy, Clone)]
 pub enum Keys {
+    A,
     Q,
     P,
+    R,
+    B,
+    C,
+    D,
+    E,
+    Enter,
+    F,
+    G,
+    H,
+    L,
+    N,
+    S,
+    V,
+    W,
+    X,
+    Z,
     CapsLock,
     Esc,
+    Zero,
     One,
     Two,
     Three,
     Four,
     Five,
     Six,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    F1,
+    F2,
+    F3,
+    F4,
+    F8,
+    F9,
     NotSupported,
 }
 
@@ -23,9 +52,38 @@ impl From<u8> for Keys {
             13 => Self::Four,
             14 => Self::Five,
             15 => Self::Six,
+            19 => Self::Zero,
             24 => Self::Q,
+            25 => Self::W,
+            26 => Self::E,
+            27 => Self::R,
+            36 => Self::Enter,
+            38 => Self::A,
             33 => Self::P,
+            39 => Self::S,
+            40 => Self::D,
+            41 => Self::F,
+            42 => Self::G,
+            43 => Self::H,
+            46 => Self::L,
+            52 => Self::Z,
+            53 => Self::X,
+            54 => Self::C,
+            55 => Self::V,
+            56 => Self::B,
+            57 => Self::N,
             66 => Self::CapsLock,
+            67 => Self::F1,
+            68 => Self::F2,
+            69 => Self::F3,
+            70 => Self::F4,
+            74 => Self::F8,
+            75 => Self::F9,
+            110 => Self::Home,
+            112 => Self::PageUp,
+            115 => Self::End,
+            117 => Self::PageDown,
+            119 => Self::Delete,
             _ => Self::NotSupported,
         }
     }
@@ -0,0 +1,106 @@
+use super::color::CurrentColor;
+use super::graphics_context::{Blending, GraphicContext, LineWidth};
+use super::movement::Movement;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ButtonPressEvent, ButtonReleaseEvent, ConnectionExt, ExposeEvent};
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+const LASER_COLOR: CurrentColor = CurrentColor::Red;
+const FADE_DURATION: Duration = Duration::from_secs(1);
+
+/// A pointer whose strokes are kept out of the stack
+/// and fade away shortly after being drawn
+#[derive(Clone, Debug)]
+pub struct Laser {
+    trails: Vec<(Movement, Option<Instant>)>,
+}
+
+impl Laser {
+    pub fn new() -> Self {
+        Self { trails: Vec::new() }
+    }
+
+    pub fn press(&mut self, ev: ButtonPressEvent, width: LineWidth) {
+        self.trails
+            .push((Movement::new(ev, LASER_COLOR, width), None));
+    }
+
+    pub fn motion(
+        &mut self,
+        conn: &impl Connection,
+        win: u32,
+        gc: u32,
+        motion: (i16, i16, u32),
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some((trail, None)) = self.trails.last_mut() {
+            conn.change_gc(
+                gc,
+                &GraphicContext::change_color(LASER_COLOR.value(), trail.width),
+            )?;
+            trail.motion(conn, win, gc, motion)?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the trail being drawn and starts fading it out
+    pub fn release(
+        &mut self,
+        conn: &impl Connection,
+        win: u32,
+        gc: u32,
+        ev: &ButtonReleaseEvent,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some((trail, fading_since @ None)) = self.trails.last_mut() {
+            trail.finish(conn, win, gc, ev)?;
+            *fading_since = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Starts fading the trail being drawn without waiting
+    /// for the release, e.g. when the tool is switched
+    pub fn finish(&mut self) {
+        if let Some((_, fading_since @ None)) = self.trails.last_mut() {
+            *fading_since = Some(Instant::now());
+        }
+    }
+
+    /// Checks if any trail is fading, so it needs to be redrawn
+    pub fn is_fading(&self) -> bool {
+        self.trails.iter().any(|(_, since)| since.is_some())
+    }
+
+    /// Forgets the trails that have faded out completely
+    pub fn fade(&mut self) {
+        self.trails
+            .retain(|(_, since)| since.map_or(true, |since| since.elapsed() < FADE_DURATION));
+    }
+
+    pub fn expose(
+        &self,
+        conn: &impl Connection,
+        win: u32,
+        gc: u32,
+        ev: &ExposeEvent,
+        blending: &Blending,
+    ) -> Result<(), Box<dyn Error>> {
+        for (trail, since) in &self.trails {
+            let opacity = since.map_or(100, |since| {
+                let left = FADE_DURATION.saturating_sub(since.elapsed());
+                u8::try_from(left.as_millis() * 100 / FADE_DURATION.as_millis()).unwrap_or(100)
+            });
+            conn.change_gc(
+                gc,
+                &GraphicContext::change_color(LASER_COLOR.value(), trail.width),
+            )?;
+            conn.change_gc(gc, &blending.color(LASER_COLOR.value(), opacity))?;
+            trail.expose(conn, win, gc, ev, blending)?;
+        }
+        conn.change_gc(gc, &Blending::solid(LASER_COLOR.value()))?;
+        Ok(())
+    }
+}
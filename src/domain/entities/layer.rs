@@ -0,0 +1,25 @@
+use super::movement::Movement;
+
+use serde::{Deserialize, Serialize};
+
+/// A named group of movements that can be hidden
+/// and cleared without touching the other groups
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    pub name: String,
+    pub is_visible: bool,
+}
+
+impl Layer {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            is_visible: true,
+        }
+    }
+
+    /// Checks if the layer of the movement is visible
+    pub fn shows(layers: &[Self], mov: &Movement) -> bool {
+        layers.get(mov.layer).map_or(true, |layer| layer.is_visible)
+    }
+}
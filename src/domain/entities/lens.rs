@@ -0,0 +1,152 @@
+use super::graphics_context::{GraphicContext, PixelFormat};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ChangeGCAux, ConnectionExt, ImageFormat, LineStyle, Rectangle};
+
+use std::convert::TryFrom;
+use std::error::Error;
+
+const LENS_SIZE: u16 = 240;
+const MIN_ZOOM: u16 = 2;
+const MAX_ZOOM: u16 = 4;
+const BORDER_COLOR: u32 = 0xffff_ffff;
+const BORDER_WIDTH: u32 = 2;
+
+/// Shows the screen under the pointer magnified
+#[derive(Clone, Debug)]
+pub struct Lens {
+    zoom: u16,
+    cursor: (i16, i16),
+    /// A copy of the screen taken before the lens was drawn,
+    /// as reading the screen again would magnify the lens too
+    source: Option<u32>,
+}
+
+impl Lens {
+    pub fn new() -> Self {
+        Self {
+            zoom: MIN_ZOOM,
+            cursor: (0, 0),
+            source: None,
+        }
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom = u16::min(self.zoom + 1, MAX_ZOOM);
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.zoom = u16::max(self.zoom - 1, MIN_ZOOM);
+    }
+
+    pub fn follow(&mut self, pos: (i16, i16)) {
+        self.cursor = pos;
+    }
+
+    /// Forgets the copy of the screen, for the next
+    /// time the lens is turned on to take a new one
+    pub fn release(&mut self, conn: &impl Connection) -> Result<(), Box<dyn Error>> {
+        if let Some(source) = self.source.take() {
+            conn.free_pixmap(source)?;
+        }
+        Ok(())
+    }
+
+    pub fn expose(
+        &mut self,
+        conn: &(impl Connection + Send + Sync),
+        win: u32,
+        gc: u32,
+        (root, area, depth): (u32, Rectangle, u8),
+    ) -> Result<(), Box<dyn Error>> {
+        let source = if let Some(source) = self.source {
+            source
+        } else {
+            let source = GraphicContext::snapshot(conn, (root, area), (win, depth), gc)?;
+            *self.source.insert(source)
+        };
+
+        // the captured area has to be inside the copy of the screen
+        let size = LENS_SIZE / self.zoom;
+        let x = clamp(self.cursor.0, size, area.width);
+        let y = clamp(self.cursor.1, size, area.height);
+
+        let format = PixelFormat::of(conn, depth)?;
+        let image = conn
+            .get_image(ImageFormat::ZPixmap, source, x, y, size, size, !0)?
+            .reply()?;
+        let data = magnify(
+            &image.data,
+            usize::from(size),
+            usize::from(self.zoom),
+            format,
+        );
+
+        let lens_size = size * self.zoom;
+        let half = i16::try_from(lens_size / 2).unwrap_or(0);
+        let (lens_x, lens_y) = (
+            self.cursor.0.saturating_sub(half),
+            self.cursor.1.saturating_sub(half),
+        );
+        conn.put_image(
+            ImageFormat::ZPixmap,
+            win,
+            gc,
+            lens_size,
+            lens_size,
+            lens_x,
+            lens_y,
+            0,
+            depth,
+            &data,
+        )?;
+
+        conn.change_gc(
+            gc,
+            &ChangeGCAux::new()
+                .foreground(BORDER_COLOR)
+                .line_width(BORDER_WIDTH)
+                .line_style(LineStyle::Solid),
+        )?;
+        conn.poly_rectangle(
+            win,
+            gc,
+            &[Rectangle {
+                x: lens_x,
+                y: lens_y,
+                width: lens_size,
+                height: lens_size,
+            }],
+        )?;
+        Ok(())
+    }
+}
+
+/// Returns the start of an area of `size` around `pos`, moved inside `0..limit`
+fn clamp(pos: i16, size: u16, limit: u16) -> i16 {
+    let start = i32::from(pos) - i32::from(size / 2);
+    let end = i32::from(limit) - i32::from(size);
+    i16::try_from(start.min(end).max(0)).unwrap_or(0)
+}
+
+/// Scales a square image up, repeating every pixel `zoom` times
+/// in both directions and making it opaque for the overlay
+fn magnify(data: &[u8], size: usize, zoom: usize, format: PixelFormat) -> Vec<u8> {
+    let row = format.row(size * zoom);
+    let mut magnified = Vec::with_capacity(row * size * zoom);
+    for source_row in data.chunks(format.row(size)).take(size) {
+        let mut line = Vec::with_capacity(row);
+        for pixel in source_row.chunks(format.bytes).take(size) {
+            for _ in 0..zoom {
+                let start = line.len();
+                line.extend(pixel);
+                format.make_opaque(&mut line[start..]);
+            }
+        }
+        line.resize(row, 0);
+        for _ in 0..zoom {
+            magnified.extend(&line);
+        }
+    }
+    magnified
+}
@@ -1,9 +1,33 @@
+pub mod background;
 pub mod color;
+pub mod eraser;
 pub mod graphics_context;
+pub mod history;
 pub mod keys;
+pub mod laser;
+pub mod layer;
+pub mod lens;
 pub mod movement;
+pub mod overlay;
+pub mod selection;
+pub mod session;
+pub mod shape;
+pub mod spotlight;
+pub mod tool;
 
+pub use background::*;
 pub use color::*;
+pub use eraser::*;
 pub use graphics_context::*;
+pub use history::*;
 pub use keys::*;
+pub use laser::*;
+pub use layer::*;
+pub use lens::*;
 pub use movement::*;
+pub use overlay::*;
+pub use selection::*;
+pub use session::*;
+pub use shape::*;
+pub use spotlight::*;
+pub use tool::*;
@@ -1,21 +1,35 @@
-use super::color::CurrentColor;
+use super::color::{CurrentColor, Fill};
+use super::graphics_context::{Blending, LineWidth};
+use super::shape::Shape;
 
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::{
-    get_motion_events, poly_line, ButtonPressEvent, ButtonReleaseEvent, ConnectionExt, CoordMode,
-    ExposeEvent, Point,
+    get_motion_events, poly_line, Arc, ButtonPressEvent, ButtonReleaseEvent, ChangeGCAux, Char2b,
+    ConnectionExt, CoordMode, ExposeEvent, Point, PolyShape,
 };
 
+use std::convert::TryFrom;
 use std::error::Error;
+use std::time::Instant;
 
 const PADDING: i16 = 5;
+const MARKER_TEXT_COLOR: u32 = 0xffff_ffff;
+const CLOSED_LOOP_DISTANCE: f32 = 20.0;
 
 /// A drawed objects, consisting of either a single
 /// rhomboid, or two rhomboids connected by a winding line
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Movement {
     pub color: CurrentColor,
+    pub width: LineWidth,
+    pub layer: usize,
+    pub shape: Shape,
+    pub fill: Option<Fill>,
     pub lines: Vec<(i16, i16)>,
+    /// When the movement disappears, if it was drawn with auto-fade on
+    pub expires: Option<Instant>,
+    /// The width and the baseline offset of the number of a marker
+    label: Option<(i16, i16)>,
 
     left: i16,
     right: i16,
@@ -27,7 +41,7 @@ pub struct Movement {
 }
 
 impl Movement {
-    pub fn new(ev: ButtonPressEvent, color: CurrentColor) -> Self {
+    pub fn new(ev: ButtonPressEvent, color: CurrentColor, width: LineWidth) -> Self {
         let left = ev.event_x - PADDING;
         let right = ev.event_x + PADDING;
         let top = ev.event_y - PADDING;
@@ -38,6 +52,12 @@ impl Movement {
 
         Self {
             color,
+            width,
+            layer: 0,
+            shape: Shape::Stroke,
+            fill: None,
+            expires: None,
+            label: None,
             left,
             right,
             top,
@@ -48,6 +68,91 @@ impl Movement {
         }
     }
 
+    /// Creates an already finished movement from a
+    /// list of coordinates, e.g. a piece of an erased one
+    pub fn from_lines(lines: Vec<(i16, i16)>, color: CurrentColor, width: LineWidth) -> Self {
+        let mut mov = Self {
+            color,
+            width,
+            layer: 0,
+            shape: Shape::Stroke,
+            fill: None,
+            expires: None,
+            label: None,
+            left: 0,
+            right: 0,
+            top: 0,
+            bottom: 0,
+            time: 0,
+            lines,
+            is_finished: true,
+        };
+        mov.update_bounds();
+        mov
+    }
+
+    /// Creates a numbered badge centered at `(x, y)`
+    pub fn marker((x, y): (i16, i16), number: u32, color: CurrentColor, width: LineWidth) -> Self {
+        let mut mov = Self {
+            shape: Shape::Marker(number),
+            ..Self::from_lines(vec![(x, y)], color, width)
+        };
+        mov.update_bounds();
+        mov
+    }
+
+    /// Measures the number of a marker with the font of the graphic
+    /// context, once, as asking the server on every expose would wait for it
+    pub fn measure(&mut self, conn: &impl Connection, gc: u32) -> Result<(), Box<dyn Error>> {
+        if let Shape::Marker(number) = self.shape {
+            let chars = number
+                .to_string()
+                .bytes()
+                .map(|byte2| Char2b { byte1: 0, byte2 })
+                .collect::<Vec<Char2b>>();
+            let extents = conn.query_text_extents(gc, &chars)?.reply()?;
+            let width = i16::try_from(extents.overall_width).unwrap_or(0);
+            let baseline = (extents.font_ascent - extents.font_descent) / 2;
+            self.label = Some((width, baseline));
+        }
+        Ok(())
+    }
+
+    /// Creates a closed outline through the coordinates
+    pub fn polygon(lines: Vec<(i16, i16)>, color: CurrentColor, width: LineWidth) -> Self {
+        Self {
+            shape: Shape::Polygon,
+            ..Self::from_lines(lines, color, width)
+        }
+    }
+
+    /// Replaces the coordinates, e.g. while a shape is being dragged out
+    pub fn set_lines(&mut self, lines: Vec<(i16, i16)>) {
+        self.lines = lines;
+        self.update_bounds();
+    }
+
+    /// Changes the line width, and with it the size of a marker
+    pub fn set_width(&mut self, width: LineWidth) {
+        self.width = width;
+        self.update_bounds();
+    }
+
+    /// Checks if the line is done, rather than still being drawn
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    /// Checks if a freehand line ends about where it started
+    pub fn is_closed_loop(&self) -> bool {
+        match (self.lines.first(), self.lines.last()) {
+            (Some(&first), Some(&last)) if self.lines.len() > 2 => {
+                distance_to_segment(first, last, last) <= CLOSED_LOOP_DISTANCE
+            }
+            _ => false,
+        }
+    }
+
     pub fn motion(
         &mut self,
         conn: &impl Connection,
@@ -139,15 +244,247 @@ impl Movement {
         Ok(())
     }
 
-    pub fn expose(&self, conn: &impl Connection, win: u32, gc: u32, ev: &ExposeEvent) {
+    pub fn expose(
+        &self,
+        conn: &impl Connection,
+        win: u32,
+        gc: u32,
+        ev: &ExposeEvent,
+        blending: &Blending,
+    ) -> Result<(), Box<dyn Error>> {
         // We should check if this object is in the exposed
         // area, but I can't be bohered right now, so just
         // redraw on the last Expose in every batch
 
         if ev.count == 0 {
-            // Redraw all the lines
-            poly_line(conn, CoordMode::Origin, win, gc, &self.points()).unwrap();
+            match self.shape {
+                // Redraw all the lines
+                Shape::Stroke | Shape::Polygon => {
+                    let points = Self::points_from(&self.outline());
+                    if let Some(fill) = self.fill.filter(|_| points.len() > 2) {
+                        let color = blending.color(fill.color.value(), fill.opacity);
+                        conn.change_gc(gc, &color)?;
+                        conn.fill_poly(win, gc, PolyShape::Complex, CoordMode::Origin, &points)?;
+                        conn.change_gc(gc, &Blending::solid(self.color.value()))?;
+                    }
+                    poly_line(conn, CoordMode::Origin, win, gc, &points)?;
+                }
+                Shape::Marker(number) => self.expose_marker(conn, win, gc, number)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws a disc in the movement color with the number centered
+    /// on it, using the font already set on the graphic context
+    fn expose_marker(
+        &self,
+        conn: &impl Connection,
+        win: u32,
+        gc: u32,
+        number: u32,
+    ) -> Result<(), Box<dyn Error>> {
+        let (x, y) = self.lines[0];
+        let radius = self.padding();
+        let diameter = u16::try_from(radius * 2).unwrap_or(0);
+        conn.poly_fill_arc(
+            win,
+            gc,
+            &[Arc {
+                x: x - radius,
+                y: y - radius,
+                width: diameter,
+                height: diameter,
+                angle1: 0,
+                angle2: 360 * 64,
+            }],
+        )?;
+
+        let text = number.to_string().into_bytes();
+        let (width, baseline) = self.label.unwrap_or_default();
+
+        let mut items = vec![u8::try_from(text.len()).unwrap_or(0), 0];
+        items.extend(text);
+        conn.change_gc(gc, &ChangeGCAux::new().foreground(MARKER_TEXT_COLOR))?;
+        conn.poly_text8(win, gc, x - width / 2, y + baseline, &items)?;
+        Ok(())
+    }
+
+    /// Removes the coordinates within `radius` of `(x, y)` and the lines
+    /// crossing it, returning the remaining pieces, or `None` if this
+    /// movement wasn't touched at all
+    pub fn erase(&self, (x, y): (i16, i16), radius: i16) -> Option<Vec<Self>> {
+        if x + radius < self.left
+            || x - radius > self.right
+            || y + radius < self.top
+            || y - radius > self.bottom
+        {
+            return None;
+        }
+
+        // Markers are erased as a whole
+        if let Shape::Marker(_) = self.shape {
+            return Some(Vec::new()).filter(|_| self.hit((x, y), radius));
+        }
+
+        let mut pieces = Vec::new();
+        let mut piece: Vec<(i16, i16)> = Vec::new();
+        let mut is_hit = false;
+
+        for point in self.outline() {
+            let previous = piece.last().copied();
+            if distance_to_segment((x, y), point, point) <= f32::from(radius) {
+                is_hit = true;
+                pieces.push(std::mem::take(&mut piece));
+                continue;
+            }
+            if let Some(previous) = previous {
+                if distance_to_segment((x, y), previous, point) <= f32::from(radius) {
+                    is_hit = true;
+                    pieces.push(std::mem::take(&mut piece));
+                }
+            }
+            piece.push(point);
+        }
+        pieces.push(piece);
+
+        if !is_hit {
+            return None;
+        }
+
+        Some(
+            pieces
+                .into_iter()
+                .filter(|lines| lines.len() > 1)
+                .map(|lines| Self {
+                    layer: self.layer,
+                    fill: self.fill,
+                    expires: self.expires,
+                    ..Self::from_lines(lines, self.color, self.width)
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the bounding box as `(left, top, right, bottom)`
+    pub fn bounds(&self) -> (i16, i16, i16, i16) {
+        (self.left, self.top, self.right, self.bottom)
+    }
+
+    /// Returns the box around all the movements
+    /// as `(left, top, right, bottom)`
+    pub fn bounds_of<'m>(
+        movements: impl IntoIterator<Item = &'m Self>,
+    ) -> Option<(i16, i16, i16, i16)> {
+        movements
+            .into_iter()
+            .map(Self::bounds)
+            .fold(None, |total, bounds| match total {
+                None => Some(bounds),
+                Some((left, top, right, bottom)) => Some((
+                    i16::min(left, bounds.0),
+                    i16::min(top, bounds.1),
+                    i16::max(right, bounds.2),
+                    i16::max(bottom, bounds.3),
+                )),
+            })
+    }
+
+    pub fn translate(&mut self, (dx, dy): (i16, i16)) {
+        for line in &mut self.lines {
+            line.0 = line.0.saturating_add(dx);
+            line.1 = line.1.saturating_add(dy);
+        }
+        self.left = self.left.saturating_add(dx);
+        self.right = self.right.saturating_add(dx);
+        self.top = self.top.saturating_add(dy);
+        self.bottom = self.bottom.saturating_add(dy);
+    }
+
+    /// Maps every coordinate through `f`, e.g. to scale or rotate them
+    pub fn transform(&mut self, f: impl Fn(f32, f32) -> (f32, f32)) {
+        for line in &mut self.lines {
+            let (x, y) = f(f32::from(line.0), f32::from(line.1));
+            *line = (to_coord(x), to_coord(y));
         }
+        self.update_bounds();
+    }
+
+    /// Checks if `(x, y)` is within `tolerance` of the lines
+    pub fn hit(&self, (x, y): (i16, i16), tolerance: i16) -> bool {
+        if x < self.left || x > self.right || y < self.top || y > self.bottom {
+            return false;
+        }
+
+        if let Shape::Marker(_) = self.shape {
+            let radius = f32::from(self.padding() + tolerance);
+            return distance_to_segment((x, y), self.lines[0], self.lines[0]) <= radius;
+        }
+
+        if self.fill.is_some() && self.contains((x, y)) {
+            return true;
+        }
+
+        let tolerance = f32::from(tolerance);
+        match self.outline().as_slice() {
+            [point] => distance_to_segment((x, y), *point, *point) <= tolerance,
+            lines => lines
+                .windows(2)
+                .any(|line| distance_to_segment((x, y), line[0], line[1]) <= tolerance),
+        }
+    }
+
+    /// Checks if `(x, y)` is inside the area closed by the lines
+    fn contains(&self, (x, y): (i16, i16)) -> bool {
+        let (x, y) = (f32::from(x), f32::from(y));
+        let mut is_inside = false;
+        let mut previous = match self.lines.last() {
+            Some(&point) => point,
+            None => return false,
+        };
+
+        for &point in &self.lines {
+            let (ax, ay) = (f32::from(previous.0), f32::from(previous.1));
+            let (bx, by) = (f32::from(point.0), f32::from(point.1));
+            if (ay > y) != (by > y) && x < ax + (y - ay) / (by - ay) * (bx - ax) {
+                is_inside = !is_inside;
+            }
+            previous = point;
+        }
+        is_inside
+    }
+
+    /// Returns the coordinates the outline goes through,
+    /// ending where it started when the shape is closed
+    fn outline(&self) -> Vec<(i16, i16)> {
+        let mut outline = self.lines.clone();
+        if let (Shape::Polygon, Some(&first)) = (self.shape, self.lines.first()) {
+            outline.push(first);
+        }
+        outline
+    }
+
+    /// Returns how far the drawing goes past the coordinates
+    fn padding(&self) -> i16 {
+        match self.shape {
+            Shape::Stroke | Shape::Polygon => PADDING,
+            Shape::Marker(_) => 10 + 4 * self.width as i16,
+        }
+    }
+
+    fn update_bounds(&mut self) {
+        let padding = self.padding();
+        let (mut left, mut right, mut top, mut bottom) = (i16::MAX, i16::MIN, i16::MAX, i16::MIN);
+        for &(x, y) in &self.lines {
+            left = i16::min(x - padding, left);
+            right = i16::max(x + padding, right);
+            top = i16::min(y - padding, top);
+            bottom = i16::max(y + padding, bottom);
+        }
+        self.left = left;
+        self.right = right;
+        self.top = top;
+        self.bottom = bottom;
     }
 
     fn points_from(lines: &[(i16, i16)]) -> Vec<Point> {
@@ -160,15 +497,98 @@ impl Movement {
         }
         points
     }
+}
 
-    fn points(&self) -> Vec<Point> {
-        let mut points = Vec::new();
-        for line in &self.lines {
-            points.push(Point {
-                x: line.0,
-                y: line.1,
-            });
+fn distance_to_segment((x, y): (i16, i16), from: (i16, i16), to: (i16, i16)) -> f32 {
+    let (px, py) = (f32::from(x), f32::from(y));
+    let (ax, ay) = (f32::from(from.0), f32::from(from.1));
+    let (bx, by) = (f32::from(to.0), f32::from(to.1));
+    let (dx, dy) = (bx - ax, by - ay);
+
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((px - ax) * dx + (py - ay) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    (px - (ax + t * dx)).hypot(py - (ay + t * dy))
+}
+
+/// Rounds a transformed coordinate back to the screen ones
+pub fn to_coord(value: f32) -> i16 {
+    value
+        .round()
+        .clamp(f32::from(i16::MIN + PADDING), f32::from(i16::MAX - PADDING)) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(lines: Vec<(i16, i16)>) -> Movement {
+        let mut mov = Movement::from_lines(lines, CurrentColor::Red, LineWidth::Thin);
+        mov.layer = 2;
+        mov
+    }
+
+    #[test]
+    fn erasing_away_from_a_movement_leaves_it_alone() {
+        let mov = stroke(vec![(0, 0), (100, 0)]);
+        assert!(mov.erase((50, 40), 5).is_none());
+    }
+
+    #[test]
+    fn erasing_the_middle_splits_a_movement_in_two() {
+        let mov = stroke((0..=10).map(|step| (step * 10, 0)).collect());
+        let pieces = mov.erase((50, 0), 5).unwrap();
+
+        let lines = pieces
+            .iter()
+            .map(|piece| piece.lines.clone())
+            .collect::<Vec<Vec<(i16, i16)>>>();
+        assert_eq!(
+            lines,
+            [
+                vec![(0, 0), (10, 0), (20, 0), (30, 0), (40, 0)],
+                vec![(60, 0), (70, 0), (80, 0), (90, 0), (100, 0)],
+            ]
+        );
+        assert!(pieces.iter().all(|piece| piece.layer == 2));
+    }
+
+    #[test]
+    fn the_pieces_fade_and_are_filled_like_the_erased_movement() {
+        let mut mov = stroke((0..=10).map(|step| (step * 10, 0)).collect());
+        mov.expires = Some(Instant::now());
+        mov.fill = Fill::new().next_opacity().visible();
+        let pieces = mov.erase((50, 0), 5).unwrap();
+
+        assert_eq!(pieces.len(), 2);
+        for piece in pieces {
+            assert_eq!(piece.expires, mov.expires);
+            assert_eq!(piece.fill, mov.fill);
         }
-        points
+    }
+
+    #[test]
+    fn erasing_across_a_line_removes_it() {
+        let mov = stroke(vec![(0, 0), (100, 0)]);
+        assert!(mov.erase((50, 3), 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn wider_markers_have_wider_bounds() {
+        let mut marker = Movement::marker((50, 50), 1, CurrentColor::Red, LineWidth::Thin);
+        let (left, ..) = marker.bounds();
+        marker.set_width(LineWidth::Wide);
+        assert!(marker.bounds().0 < left);
+        assert!(marker.hit((50 - marker.padding() + 1, 50), 0));
+    }
+
+    #[test]
+    fn markers_are_erased_as_a_whole() {
+        let marker = Movement::marker((50, 50), 1, CurrentColor::Red, LineWidth::Thin);
+        assert!(marker.erase((52, 50), 5).unwrap().is_empty());
     }
 }
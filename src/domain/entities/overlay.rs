@@ -0,0 +1,10 @@
+/// How the window is shown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overlay {
+    /// Shown, taking the clicks and the keys to draw
+    Drawing,
+    /// Shown, letting the clicks and the keys through to the windows below
+    Passthrough,
+    /// Unmapped, until the daemon hotkey shows it again
+    Hidden,
+}
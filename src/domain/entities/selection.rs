@@ -0,0 +1,574 @@
+use super::layer::Layer;
+use super::movement::Movement;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Arc, ConnectionExt, Rectangle, Segment};
+
+use std::convert::TryFrom;
+use std::error::Error;
+
+const HIT_TOLERANCE: i16 = 6;
+const HANDLE_SIZE: i16 = 7;
+const ROTATION_HANDLE_OFFSET: i16 = 20;
+
+#[derive(Clone, Debug)]
+enum Drag {
+    Move {
+        last: (i16, i16),
+    },
+    Band {
+        from: (i16, i16),
+        to: (i16, i16),
+        extend: bool,
+    },
+    Scale {
+        anchor: (f32, f32),
+        corner: (f32, f32),
+        originals: Vec<(usize, Movement)>,
+    },
+    Rotate {
+        center: (f32, f32),
+        angle: f32,
+        originals: Vec<(usize, Movement)>,
+    },
+}
+
+/// Where to move the selected movements in the drawing order
+#[derive(Clone, Copy, Debug)]
+pub enum ZOrder {
+    Raise,
+    Lower,
+    Front,
+    Back,
+}
+
+/// The movements picked with the select tool, by their
+/// position in the stack, and the drag being done with them
+#[derive(Clone, Debug)]
+pub struct Selection {
+    pub items: Vec<usize>,
+    drag: Option<Drag>,
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            drag: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.drag.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.drag = None;
+    }
+
+    /// Starts a drag at `pos`: over a corner handle the selection gets
+    /// scaled, over the rotation handle it gets rotated, over a movement
+    /// it gets selected and moved, anywhere else a rubber band selection
+    /// starts. With `extend` the picked movements are added to the selection
+    pub fn press(
+        &mut self,
+        stack: &[Option<Movement>],
+        layers: &[Layer],
+        pos: (i16, i16),
+        extend: bool,
+    ) {
+        if let Some(drag) = self.grab_handle(stack, pos) {
+            self.drag = Some(drag);
+        } else if let Some(index) = Self::pick(stack, layers, pos) {
+            if !self.items.contains(&index) {
+                if !extend {
+                    self.items.clear();
+                }
+                self.items.push(index);
+            }
+            self.drag = Some(Drag::Move { last: pos });
+        } else {
+            if !extend {
+                self.items.clear();
+            }
+            self.drag = Some(Drag::Band {
+                from: pos,
+                to: pos,
+                extend,
+            });
+        }
+    }
+
+    /// Moves, scales or rotates the selected movements or stretches
+    /// the rubber band, returning whether the screen needs to be updated
+    pub fn motion(&mut self, stack: &mut [Option<Movement>], pos: (i16, i16)) -> bool {
+        let (x, y) = (f32::from(pos.0), f32::from(pos.1));
+
+        match &mut self.drag {
+            Some(Drag::Move { last }) => {
+                let offset = (pos.0 - last.0, pos.1 - last.1);
+                *last = pos;
+                for &index in &self.items {
+                    if let Some(Some(mov)) = stack.get_mut(index) {
+                        mov.translate(offset);
+                    }
+                }
+                offset != (0, 0)
+            }
+            Some(Drag::Band { to, .. }) => {
+                *to = pos;
+                true
+            }
+            Some(Drag::Scale {
+                anchor,
+                corner,
+                originals,
+            }) => {
+                let (ax, ay) = *anchor;
+                let scale_x = scale(x - ax, corner.0 - ax);
+                let scale_y = scale(y - ay, corner.1 - ay);
+                Self::apply(stack, originals, |px, py| {
+                    (ax + (px - ax) * scale_x, ay + (py - ay) * scale_y)
+                });
+                true
+            }
+            Some(Drag::Rotate {
+                center,
+                angle,
+                originals,
+            }) => {
+                let (cx, cy) = *center;
+                let (sin, cos) = ((y - cy).atan2(x - cx) - *angle).sin_cos();
+                Self::apply(stack, originals, |px, py| {
+                    let (dx, dy) = (px - cx, py - cy);
+                    (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ends the drag, selecting everything visible inside the rubber band
+    pub fn release(&mut self, stack: &[Option<Movement>], layers: &[Layer]) {
+        if let Some(Drag::Band { from, to, extend }) = self.drag.take() {
+            if !extend {
+                self.items.clear();
+            }
+            let (left, top) = (i16::min(from.0, to.0), i16::min(from.1, to.1));
+            let (right, bottom) = (i16::max(from.0, to.0), i16::max(from.1, to.1));
+
+            for (index, mov) in stack.iter().enumerate() {
+                if let Some(mov) = mov.as_ref().filter(|m| Layer::shows(layers, m)) {
+                    let bounds = mov.bounds();
+                    if bounds.0 >= left
+                        && bounds.1 >= top
+                        && bounds.2 <= right
+                        && bounds.3 <= bottom
+                        && !self.items.contains(&index)
+                    {
+                        self.items.push(index);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the selected movements
+    pub fn selected<'s>(
+        &'s self,
+        stack: &'s mut [Option<Movement>],
+    ) -> impl Iterator<Item = &'s mut Movement> + 's {
+        stack
+            .iter_mut()
+            .enumerate()
+            .filter(move |(index, _)| self.items.contains(index))
+            .filter_map(|(_, mov)| mov.as_mut())
+    }
+
+    /// Returns copies of the selected movements, in stack order
+    pub fn copy(&self, stack: &[Option<Movement>]) -> Vec<Movement> {
+        stack
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.items.contains(index))
+            .filter_map(|(_, mov)| mov.clone())
+            .collect()
+    }
+
+    /// Moves the selected movements up or down the stack, changing
+    /// which ones are drawn over the others
+    pub fn reorder(&mut self, stack: &mut Vec<Option<Movement>>, order: ZOrder) {
+        let mut entries = stack
+            .drain(..)
+            .enumerate()
+            .map(|(index, mov)| (self.items.contains(&index), mov))
+            .collect::<Vec<(bool, Option<Movement>)>>();
+
+        match order {
+            ZOrder::Raise => {
+                for index in (1..entries.len()).rev() {
+                    if entries[index - 1].0 && !entries[index].0 {
+                        entries.swap(index - 1, index);
+                    }
+                }
+            }
+            ZOrder::Lower => {
+                for index in 1..entries.len() {
+                    if entries[index].0 && !entries[index - 1].0 {
+                        entries.swap(index - 1, index);
+                    }
+                }
+            }
+            // stable sorts keep the order between the selected movements
+            ZOrder::Front => entries.sort_by_key(|&(is_selected, _)| is_selected),
+            ZOrder::Back => entries.sort_by_key(|&(is_selected, _)| !is_selected),
+        }
+
+        self.items.clear();
+        for (index, (is_selected, mov)) in entries.into_iter().enumerate() {
+            if is_selected {
+                self.items.push(index);
+            }
+            stack.push(mov);
+        }
+    }
+
+    /// Removes the movements `keep` turns down from the stack,
+    /// keeping the same movements selected
+    pub fn retain(
+        &mut self,
+        stack: &mut Vec<Option<Movement>>,
+        mut keep: impl FnMut(&Option<Movement>) -> bool,
+    ) {
+        // where each movement ends up, if it stays
+        let mut indices = Vec::with_capacity(stack.len());
+        let mut next = 0;
+        stack.retain(|mov| {
+            let is_kept = keep(mov);
+            indices.push(Some(next).filter(|_| is_kept));
+            next += usize::from(is_kept);
+            is_kept
+        });
+        let renumber = |index: usize| indices.get(index).copied().flatten();
+
+        self.items = self
+            .items
+            .iter()
+            .filter_map(|&index| renumber(index))
+            .collect();
+        if let Some(Drag::Scale { originals, .. } | Drag::Rotate { originals, .. }) = &mut self.drag
+        {
+            *originals = originals
+                .drain(..)
+                .filter_map(|(index, mov)| Some((renumber(index)?, mov)))
+                .collect();
+        }
+    }
+
+    /// Removes the selected movements from the stack
+    pub fn delete(&mut self, stack: &mut Vec<Option<Movement>>) {
+        let mut index = 0;
+        stack.retain(|_| {
+            index += 1;
+            !self.items.contains(&(index - 1))
+        });
+        self.clear();
+    }
+
+    pub fn expose(
+        &self,
+        conn: &impl Connection,
+        win: u32,
+        gc: u32,
+        stack: &[Option<Movement>],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut frames = Vec::new();
+        let mut handles = Vec::new();
+        let mut segments = Vec::new();
+        let mut arcs = Vec::new();
+
+        for &index in &self.items {
+            if let Some(Some(mov)) = stack.get(index) {
+                let (left, top, right, bottom) = mov.bounds();
+                frames.push(rectangle((left, top), (right, bottom)));
+            }
+        }
+
+        if let Some(bounds) = self.bounds(stack) {
+            let (left, top, right, bottom) = bounds;
+            if self.items.len() > 1 {
+                frames.push(rectangle((left, top), (right, bottom)));
+            }
+
+            for &(x, y) in &corners(bounds) {
+                handles.push(rectangle(
+                    (x - HANDLE_SIZE / 2, y - HANDLE_SIZE / 2),
+                    (x + HANDLE_SIZE / 2, y + HANDLE_SIZE / 2),
+                ));
+            }
+
+            let (x, y) = rotation_handle(bounds);
+            segments.push(Segment {
+                x1: x,
+                y1: top,
+                x2: x,
+                y2: y,
+            });
+            arcs.push(Arc {
+                x: x - HANDLE_SIZE / 2,
+                y: y - HANDLE_SIZE / 2,
+                width: HANDLE_SIZE.unsigned_abs(),
+                height: HANDLE_SIZE.unsigned_abs(),
+                angle1: 0,
+                angle2: 360 * 64,
+            });
+        }
+
+        if let Some(Drag::Band { from, to, .. }) = self.drag {
+            frames.push(rectangle(
+                (i16::min(from.0, to.0), i16::min(from.1, to.1)),
+                (i16::max(from.0, to.0), i16::max(from.1, to.1)),
+            ));
+        }
+
+        conn.poly_rectangle(win, gc, &frames)?;
+        conn.poly_segment(win, gc, &segments)?;
+        conn.poly_fill_rectangle(win, gc, &handles)?;
+        conn.poly_fill_arc(win, gc, &arcs)?;
+        Ok(())
+    }
+
+    /// Returns the box around all the selected movements
+    /// as `(left, top, right, bottom)`
+    fn bounds(&self, stack: &[Option<Movement>]) -> Option<(i16, i16, i16, i16)> {
+        Movement::bounds_of(
+            self.items
+                .iter()
+                .filter_map(|&index| stack.get(index).and_then(Option::as_ref)),
+        )
+    }
+
+    /// Starts scaling when `pos` is over a corner handle, scaling
+    /// from the opposite corner, or rotating when it's over the
+    /// rotation handle, rotating around the center
+    fn grab_handle(&self, stack: &[Option<Movement>], pos: (i16, i16)) -> Option<Drag> {
+        let bounds = self.bounds(stack)?;
+        let (left, top, right, bottom) = bounds;
+        let is_over = |(x, y): (i16, i16)| {
+            (pos.0 - x).abs() <= HANDLE_SIZE && (pos.1 - y).abs() <= HANDLE_SIZE
+        };
+        let originals = || {
+            self.items
+                .iter()
+                .filter_map(|&index| match stack.get(index) {
+                    Some(Some(mov)) => Some((index, mov.clone())),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        if is_over(rotation_handle(bounds)) {
+            let center = (
+                (f32::from(left) + f32::from(right)) / 2.0,
+                (f32::from(top) + f32::from(bottom)) / 2.0,
+            );
+            return Some(Drag::Rotate {
+                center,
+                angle: (f32::from(pos.1) - center.1).atan2(f32::from(pos.0) - center.0),
+                originals: originals(),
+            });
+        }
+
+        let corner = corners(bounds)
+            .iter()
+            .copied()
+            .find(|&corner| is_over(corner))?;
+        let anchor = (
+            if corner.0 == left { right } else { left },
+            if corner.1 == top { bottom } else { top },
+        );
+        Some(Drag::Scale {
+            anchor: (f32::from(anchor.0), f32::from(anchor.1)),
+            corner: (f32::from(corner.0), f32::from(corner.1)),
+            originals: originals(),
+        })
+    }
+
+    /// Replaces the selected movements by their
+    /// original geometry mapped through `f`
+    fn apply(
+        stack: &mut [Option<Movement>],
+        originals: &[(usize, Movement)],
+        f: impl Fn(f32, f32) -> (f32, f32),
+    ) {
+        for (index, original) in originals {
+            if let Some(Some(mov)) = stack.get_mut(*index) {
+                *mov = original.clone();
+                mov.transform(&f);
+            }
+        }
+    }
+
+    /// Finds the topmost visible movement under `pos`
+    fn pick(stack: &[Option<Movement>], layers: &[Layer], pos: (i16, i16)) -> Option<usize> {
+        stack.iter().rposition(|mov| {
+            mov.as_ref().map_or(false, |m| {
+                Layer::shows(layers, m) && m.hit(pos, HIT_TOLERANCE)
+            })
+        })
+    }
+}
+
+fn corners((left, top, right, bottom): (i16, i16, i16, i16)) -> [(i16, i16); 4] {
+    [(left, top), (right, top), (left, bottom), (right, bottom)]
+}
+
+fn rotation_handle((left, top, right, _): (i16, i16, i16, i16)) -> (i16, i16) {
+    (left + (right - left) / 2, top - ROTATION_HANDLE_OFFSET)
+}
+
+/// Returns how much a distance from the anchor was
+/// stretched, keeping tiny drags from flattening it
+fn scale(distance: f32, original: f32) -> f32 {
+    if original.abs() < 1.0 {
+        return 1.0;
+    }
+    let scale = distance / original;
+    if scale.abs() < 0.05 {
+        0.05_f32.copysign(scale)
+    } else {
+        scale
+    }
+}
+
+fn rectangle((left, top): (i16, i16), (right, bottom): (i16, i16)) -> Rectangle {
+    Rectangle {
+        x: left,
+        y: top,
+        width: u16::try_from(right - left).unwrap_or(0),
+        height: u16::try_from(bottom - top).unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{CurrentColor, LineWidth};
+
+    fn stack(xs: &[i16]) -> Vec<Option<Movement>> {
+        xs.iter()
+            .map(|&x| {
+                let lines = vec![(x, 0), (x, 10)];
+                Some(Movement::from_lines(
+                    lines,
+                    CurrentColor::Red,
+                    LineWidth::Thin,
+                ))
+            })
+            .collect()
+    }
+
+    fn xs(stack: &[Option<Movement>]) -> Vec<i16> {
+        stack.iter().flatten().map(|mov| mov.lines[0].0).collect()
+    }
+
+    #[test]
+    fn retain_keeps_the_same_movements_selected() {
+        let mut stack = stack(&[0, 1, 2, 3]);
+        let mut selection = Selection::new();
+        selection.items = vec![1, 3];
+
+        selection.retain(&mut stack, |mov| {
+            mov.as_ref().map_or(false, |mov| mov.lines[0].0 != 0)
+        });
+
+        assert_eq!(xs(&stack), [1, 2, 3]);
+        assert_eq!(selection.items, [0, 2]);
+    }
+
+    #[test]
+    fn retain_forgets_the_removed_movements() {
+        let mut stack = stack(&[0, 1, 2]);
+        let mut selection = Selection::new();
+        selection.items = vec![0, 1];
+
+        selection.retain(&mut stack, |mov| {
+            mov.as_ref().map_or(false, |mov| mov.lines[0].0 != 1)
+        });
+
+        assert_eq!(xs(&stack), [0, 2]);
+        assert_eq!(selection.items, [0]);
+    }
+
+    fn reorder(order: ZOrder) -> (Vec<i16>, Vec<usize>) {
+        let mut stack = stack(&[0, 1, 2, 3, 4]);
+        let mut selection = Selection::new();
+        selection.items = vec![1, 3];
+        selection.reorder(&mut stack, order);
+        (xs(&stack), selection.items)
+    }
+
+    #[test]
+    fn raising_moves_each_selected_movement_one_step_up() {
+        assert_eq!(reorder(ZOrder::Raise), (vec![0, 2, 1, 4, 3], vec![2, 4]));
+    }
+
+    #[test]
+    fn lowering_moves_each_selected_movement_one_step_down() {
+        assert_eq!(reorder(ZOrder::Lower), (vec![1, 0, 3, 2, 4], vec![0, 2]));
+    }
+
+    #[test]
+    fn bringing_to_the_front_keeps_the_selected_movements_in_order() {
+        assert_eq!(reorder(ZOrder::Front), (vec![0, 2, 4, 1, 3], vec![3, 4]));
+    }
+
+    #[test]
+    fn sending_to_the_back_keeps_the_selected_movements_in_order() {
+        assert_eq!(reorder(ZOrder::Back), (vec![1, 3, 0, 2, 4], vec![0, 1]));
+    }
+
+    #[test]
+    fn the_topmost_movement_can_not_be_raised_further() {
+        let mut stack = stack(&[0, 1]);
+        let mut selection = Selection::new();
+        selection.items = vec![1];
+        selection.reorder(&mut stack, ZOrder::Raise);
+        assert_eq!(xs(&stack), [0, 1]);
+        assert_eq!(selection.items, [1]);
+    }
+
+    /// Drags a handle of a selected corner shaped
+    /// stroke from `from` to `to`, returning the stroke
+    fn drag_handle(from: (i16, i16), to: (i16, i16)) -> Movement {
+        let lines = vec![(10, 10), (50, 10), (50, 30)];
+        let mut stack = vec![Some(Movement::from_lines(
+            lines,
+            CurrentColor::Red,
+            LineWidth::Thin,
+        ))];
+        let mut selection = Selection::new();
+        selection.items = vec![0];
+
+        selection.press(&stack, &[], from, false);
+        assert!(selection.motion(&mut stack, to));
+        selection.release(&stack, &[]);
+        stack.remove(0).unwrap()
+    }
+
+    #[test]
+    fn dragging_a_corner_scales_from_the_opposite_one() {
+        let mov = drag_handle((55, 35), (105, 65));
+        assert_eq!(mov.lines, [(15, 15), (95, 15), (95, 55)]);
+        assert_eq!(mov.bounds(), (10, 10, 100, 60));
+    }
+
+    #[test]
+    fn dragging_the_rotation_handle_rotates_around_the_center() {
+        let mov = drag_handle((30, 5 - ROTATION_HANDLE_OFFSET), (60, 20));
+        assert_eq!(mov.lines, [(40, 0), (40, 40), (20, 40)]);
+        assert_eq!(mov.bounds(), (15, -5, 45, 45));
+    }
+}
@@ -0,0 +1,216 @@
+use super::color::{CurrentColor, Fill, FILL_OPACITIES};
+use super::graphics_context::LineWidth;
+use super::layer::Layer;
+use super::movement::Movement;
+use super::shape::Shape;
+
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::fs;
+use std::iter;
+use std::path::Path;
+
+// older files can still be read, newer ones can't
+const VERSION: u32 = 1;
+
+/// The drawings and layers saved to a file,
+/// to be opened again in a later presentation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    version: u32,
+    pub layers: Vec<Layer>,
+    drawings: Vec<Drawing>,
+}
+
+/// A movement as it is written down, leaving out
+/// what only matters while it's being drawn
+#[derive(Debug, Serialize, Deserialize)]
+struct Drawing {
+    color: CurrentColor,
+    width: LineWidth,
+    layer: usize,
+    shape: Shape,
+    fill: Option<Fill>,
+    lines: Vec<(i16, i16)>,
+}
+
+impl Session {
+    pub fn new(stack: &[Option<Movement>], layers: &[Layer]) -> Self {
+        let drawings = stack
+            .iter()
+            .flatten()
+            .map(|mov| Drawing {
+                color: mov.color,
+                width: mov.width,
+                layer: mov.layer,
+                shape: mov.shape,
+                fill: mov.fill,
+                lines: mov.lines.clone(),
+            })
+            .collect();
+
+        Self {
+            version: VERSION,
+            layers: layers.to_vec(),
+            drawings,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let session: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if session.version > VERSION {
+            return Err(format!(
+                "it was saved by a newer pincel, in version {} of the format",
+                session.version
+            )
+            .into());
+        }
+        session.check()?;
+        Ok(session)
+    }
+
+    /// Checks the drawings can be drawn, as the file
+    /// could have been written by hand or cut short
+    fn check(&self) -> Result<(), String> {
+        if self.layers.is_empty() {
+            return Err(String::from("it has no layers"));
+        }
+        for (index, drawing) in self.drawings.iter().enumerate() {
+            let colors = drawing.fill.iter().map(|fill| fill.color);
+            if iter::once(drawing.color)
+                .chain(colors)
+                .any(|color| color == CurrentColor::NotSupported)
+            {
+                return Err(format!("drawing {index} has an unknown color"));
+            }
+            if let Some(fill) = drawing.fill {
+                if !FILL_OPACITIES.contains(&fill.opacity) {
+                    return Err(format!(
+                        "drawing {index} is filled {}% opaque, not 0, 25, 50 or 100%",
+                        fill.opacity
+                    ));
+                }
+            }
+            if drawing.layer >= self.layers.len() {
+                return Err(format!(
+                    "drawing {index} is on layer {}, of {}",
+                    drawing.layer,
+                    self.layers.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Returns the drawings, ready to be put on the stack
+    pub fn movements(&self) -> Vec<Option<Movement>> {
+        self.drawings
+            .iter()
+            .filter(|drawing| !drawing.lines.is_empty())
+            .map(|drawing| {
+                let lines = drawing.lines.clone();
+                let mut mov = match drawing.shape {
+                    Shape::Stroke => Movement::from_lines(lines, drawing.color, drawing.width),
+                    Shape::Polygon => Movement::polygon(lines, drawing.color, drawing.width),
+                    Shape::Marker(number) => {
+                        Movement::marker(lines[0], number, drawing.color, drawing.width)
+                    }
+                };
+                mov.layer = drawing.layer;
+                mov.fill = drawing.fill;
+                Some(mov)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn file(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("pincel-test-{}-{name}.json", process::id()))
+    }
+
+    fn load(name: &str, contents: &str) -> Result<Session, String> {
+        let path = file(name);
+        fs::write(&path, contents).unwrap();
+        let session = Session::load(&path).map_err(|e| e.to_string());
+        fs::remove_file(&path).unwrap();
+        session
+    }
+
+    fn drawing(layer: usize, color: &str, opacity: u8) -> String {
+        format!(
+            r#"{{"version": 1, "layers": [{{"name": "notes", "is_visible": true}}],
+                "drawings": [{{"color": "{color}", "width": "thin", "layer": {layer},
+                "shape": "stroke", "fill": {{"color": "blue", "opacity": {opacity}}},
+                "lines": [[0, 0], [10, 10]]}}]}}"#
+        )
+    }
+
+    #[test]
+    fn drawings_are_the_same_after_being_saved_and_loaded() {
+        let layers = vec![Layer::new("notes"), Layer::new("diagram")];
+        let mut filled = Movement::polygon(
+            vec![(0, 0), (10, 0), (10, 10)],
+            CurrentColor::Red,
+            LineWidth::Wide,
+        );
+        filled.fill = Some(Fill {
+            color: CurrentColor::Blue,
+            opacity: 25,
+        });
+        let mut marker = Movement::marker((5, 5), 3, CurrentColor::Black, LineWidth::Thin);
+        marker.layer = 1;
+        let stroke = Movement::from_lines(
+            vec![(1, 2), (3, 4), (5, 6)],
+            CurrentColor::Green,
+            LineWidth::Normal,
+        );
+        let stack = vec![Some(filled), None, Some(marker), Some(stroke)];
+
+        let path = file("round-trip");
+        Session::new(&stack, &layers).save(&path).unwrap();
+        let session = Session::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(session.layers, layers);
+        let expected = stack.into_iter().flatten().map(Some).collect::<Vec<_>>();
+        assert_eq!(session.movements(), expected);
+    }
+
+    #[test]
+    fn well_formed_drawings_are_loaded() {
+        assert!(load("well-formed", &drawing(0, "red", 50)).is_ok());
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let contents = drawing(0, "red", 50).replace("\"version\": 1", "\"version\": 2");
+        let error = load("newer", &contents).unwrap_err();
+        assert!(error.contains("newer pincel"), "{}", error);
+    }
+
+    #[test]
+    fn drawings_that_can_not_be_drawn_are_refused() {
+        let errors = [
+            ("color", drawing(0, "notsupported", 50), "unknown color"),
+            ("opacity", drawing(0, "red", 30), "filled 30% opaque"),
+            ("layer", drawing(1, "red", 50), "on layer 1, of 1"),
+        ];
+        for (name, contents, error) in errors {
+            let found = load(name, &contents).unwrap_err();
+            assert!(found.contains(error), "{}: {}", name, found);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use super::movement::to_coord;
+
+use serde::{Deserialize, Serialize};
+
+/// What a movement draws with its coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    /// A line through all the coordinates
+    Stroke,
+    /// A numbered badge centered at the first coordinate
+    Marker(u32),
+    /// A closed outline through all the coordinates
+    Polygon,
+}
+
+const ELLIPSE_POINTS: u16 = 64;
+
+impl Shape {
+    /// Returns the corners of the rectangle with opposite corners at `from` and `to`
+    pub fn rectangle(from: (i16, i16), to: (i16, i16)) -> Vec<(i16, i16)> {
+        vec![from, (to.0, from.1), to, (from.0, to.1)]
+    }
+
+    /// Returns the outline of the ellipse inside the
+    /// rectangle with opposite corners at `from` and `to`
+    pub fn ellipse(from: (i16, i16), to: (i16, i16)) -> Vec<(i16, i16)> {
+        let (left, right) = (f32::from(from.0), f32::from(to.0));
+        let (top, bottom) = (f32::from(from.1), f32::from(to.1));
+        let center = ((left + right) / 2.0, (top + bottom) / 2.0);
+        let radius = ((right - left).abs() / 2.0, (bottom - top).abs() / 2.0);
+
+        (0..ELLIPSE_POINTS)
+            .map(|point| {
+                let angle = f32::from(point) / f32::from(ELLIPSE_POINTS) * std::f32::consts::TAU;
+                let (sin, cos) = angle.sin_cos();
+                (
+                    to_coord(center.0 + radius.0 * cos),
+                    to_coord(center.1 + radius.1 * sin),
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,116 @@
+use super::graphics_context::Blending;
+use super::shape::Shape;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt, CoordMode, Point, PolyShape};
+
+use std::error::Error;
+
+const DIM_COLOR: u32 = 0xff00_0000;
+const DIM_OPACITY: u8 = 63;
+const CIRCLE_RADIUS: i16 = 120;
+const RECTANGLE_SIZE: (i16, i16) = (320, 200);
+
+/// The outline of the clear region
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Spot {
+    Circle,
+    Rectangle,
+}
+
+/// Dims the whole screen except a region that
+/// follows the pointer, or that was dragged out
+#[derive(Clone, Debug)]
+pub struct Spotlight {
+    pub spot: Spot,
+    cursor: (i16, i16),
+    region: Option<((i16, i16), (i16, i16))>,
+}
+
+impl Spotlight {
+    pub fn new() -> Self {
+        Self {
+            spot: Spot::Circle,
+            cursor: (0, 0),
+            region: None,
+        }
+    }
+
+    pub fn toggle_spot(&mut self) {
+        self.spot = match self.spot {
+            Spot::Circle => Spot::Rectangle,
+            Spot::Rectangle => Spot::Circle,
+        };
+    }
+
+    /// Starts dragging out a region that stays where it is
+    pub fn press(&mut self, pos: (i16, i16)) {
+        self.region = Some((pos, pos));
+    }
+
+    /// Moves the clear region, returning whether it moved
+    pub fn motion(&mut self, pos: (i16, i16), is_dragging: bool) -> bool {
+        self.cursor = pos;
+        match &mut self.region {
+            Some((_, to)) if is_dragging => *to = pos,
+            Some(_) => return false,
+            None => {}
+        }
+        true
+    }
+
+    /// Keeps the dragged region, or follows the
+    /// pointer again after a click without dragging
+    pub fn release(&mut self) {
+        if let Some((from, to)) = self.region {
+            if from == to {
+                self.region = None;
+            }
+        }
+    }
+
+    pub fn expose(
+        &self,
+        conn: &impl Connection,
+        win: u32,
+        gc: u32,
+        (width, height): (i16, i16),
+        blending: &Blending,
+    ) -> Result<(), Box<dyn Error>> {
+        // the hole is cut with the even-odd rule, going around the screen
+        // and then around the region from the same corner
+        let mut outline = vec![(0, 0), (width, 0), (width, height), (0, height), (0, 0)];
+        let hole = self.hole();
+        outline.extend(&hole);
+        outline.extend(hole.first());
+        outline.push((0, 0));
+
+        let points = outline
+            .into_iter()
+            .map(|(x, y)| Point { x, y })
+            .collect::<Vec<Point>>();
+        conn.change_gc(gc, &blending.color(DIM_COLOR, DIM_OPACITY))?;
+        conn.fill_poly(win, gc, PolyShape::Complex, CoordMode::Origin, &points)?;
+        conn.change_gc(gc, &Blending::solid(DIM_COLOR))?;
+        Ok(())
+    }
+
+    fn hole(&self) -> Vec<(i16, i16)> {
+        let (from, to) = self.region.unwrap_or_else(|| {
+            let (x, y) = self.cursor;
+            let (half_width, half_height) = match self.spot {
+                Spot::Circle => (CIRCLE_RADIUS, CIRCLE_RADIUS),
+                Spot::Rectangle => (RECTANGLE_SIZE.0 / 2, RECTANGLE_SIZE.1 / 2),
+            };
+            (
+                (x.saturating_sub(half_width), y.saturating_sub(half_height)),
+                (x.saturating_add(half_width), y.saturating_add(half_height)),
+            )
+        });
+
+        match self.spot {
+            Spot::Circle => Shape::ellipse(from, to),
+            Spot::Rectangle => Shape::rectangle(from, to),
+        }
+    }
+}
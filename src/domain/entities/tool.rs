@@ -0,0 +1,35 @@
+use std::convert::TryFrom;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tool {
+    Brush,
+    Eraser,
+    Select,
+    Marker,
+    Rectangle,
+    Ellipse,
+    Polygon,
+    Laser,
+    Spotlight,
+    Zoom,
+}
+
+impl TryFrom<&str> for Tool {
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "brush" => Ok(Self::Brush),
+            "eraser" => Ok(Self::Eraser),
+            "select" => Ok(Self::Select),
+            "marker" => Ok(Self::Marker),
+            "rectangle" => Ok(Self::Rectangle),
+            "ellipse" => Ok(Self::Ellipse),
+            "polygon" => Ok(Self::Polygon),
+            "laser" => Ok(Self::Laser),
+            "spotlight" => Ok(Self::Spotlight),
+            "zoom" => Ok(Self::Zoom),
+            _ => Err(format!("unknown tool \"{name}\"")),
+        }
+    }
+}
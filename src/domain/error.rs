@@ -17,6 +17,9 @@ pub enum PincelError {
     ConnectError(ConnectError),
     XlibError(x11rb::protocol::Error),
     GenericError(Box<dyn Error>),
+    IoError(std::io::Error),
+    /// The pixels of images of this depth can't be copied
+    UnsupportedDepth(u8),
 }
 
 impl Display for PincelError {
@@ -29,6 +32,10 @@ impl Display for PincelError {
             Self::ConnectError(err) => return write!(f, "Error: {:?}", err),
             Self::XlibError(err) => return write!(f, "Error: {:?}", err),
             Self::GenericError(err) => return write!(f, "Error: {:?}", err),
+            Self::IoError(err) => return write!(f, "Error: {:?}", err),
+            Self::UnsupportedDepth(depth) => {
+                return write!(f, "Error: images of depth {} aren't supported", depth)
+            }
         }
     }
 }
@@ -70,3 +77,9 @@ impl From<x11rb::protocol::Error> for PincelError {
         Self::XlibError(e)
     }
 }
+
+impl From<std::io::Error> for PincelError {
+    fn from(e: std::io::Error) -> Self {
+        PincelError::IoError(e)
+    }
+}
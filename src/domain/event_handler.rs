@@ -1,15 +1,29 @@
+use std::convert::TryFrom;
 use std::slice;
+use std::time::{Duration, Instant};
 
 use crate::application::app::Application;
-use crate::domain::{CurrentColor, CurrentColorSingleton, GraphicContext, Keys, Movement, Result};
+use crate::domain::{
+    CurrentColor, CurrentColorSingleton, Fill, GraphicContext, Keys, Layer, Movement, Overlay,
+    PincelError, Result, Shape, Tool, ZOrder,
+};
 use chrono::prelude::*;
 use image::RgbImage;
 use x11::xlib;
 use x11rb::{
     connection::Connection,
-    protocol::{xproto::ConnectionExt, Event},
+    protocol::{
+        shape::SK,
+        xfixes::ConnectionExt as _,
+        xproto::{ConnectionExt, InputFocus, KeyButMask},
+        Event,
+    },
+    CURRENT_TIME, NONE,
+};
+use Keys::{
+    CapsLock, Delete, End, Enter, Esc, Five, Four, Home, One, PageDown, PageUp, Six, Three, Two,
+    Zero, A, B, C, D, E, F, F1, F2, F3, F4, F8, F9, G, H, L, N, P, Q, R, S, V, W, X, Z,
 };
-use Keys::{CapsLock, Esc, Five, Four, One, Six, Three, Two, P, Q};
 
 use super::LineWidth;
 
@@ -26,6 +40,9 @@ const MIDDLE_MOUSE_BUTTON: u8 = 2;
 const RIGHT_MOUSE_BUTTON: u8 = 3;
 const SCROLL_WHEEL_UP: u8 = 4;
 const SCROLL_WHEEL_DOWN: u8 = 5;
+const DUPLICATE_OFFSET: i16 = 20;
+const DOUBLE_CLICK_TIME: u32 = 400;
+const DOUBLE_CLICK_DISTANCE: i16 = 4;
 
 pub struct EventHandler<'c, C>
 where
@@ -38,36 +55,174 @@ where
 impl<C: Connection + Send + Sync> EventHandler<'_, C> {
     pub fn key_press_handler(&mut self) -> Result {
         if let Event::KeyPress(e) = self.event {
+            if e.state & u16::from(KeyButMask::Control) != 0 {
+                match e.detail.into() {
+                    C => self.copy_selection(),
+                    V => self.paste((e.event_x, e.event_y))?,
+                    D => self.duplicate_selection()?,
+                    S => {
+                        if let Err(e) = self.app.save_session(None) {
+                            println!("Could not save the drawings: {e}");
+                        }
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+
             match e.detail.into() {
-                Q | CapsLock | Esc => self.exit(),
-                One | Two | Three | Four | Five | Six => self.switch_color(e.detail.into()),
+                Q | CapsLock | Esc => self.exit()?,
+                One | Two | Three | Four | Five | Six => {
+                    if e.state & u16::from(KeyButMask::Shift) != 0 {
+                        let color = CurrentColor::from(Self::color_name(e.detail.into()));
+                        self.refill(|fill| Fill { color, ..fill })?;
+                    } else {
+                        self.switch_color(e.detail.into())?;
+                    }
+                }
                 P => self.save_screenshot()?,
+                B | E | S | N | R | C | G | L => self.switch_tool(e.detail.into())?,
+                Z => {
+                    self.app.lens.follow((e.event_x, e.event_y));
+                    self.switch_tool(Z)?;
+                }
+                H => {
+                    if e.state & u16::from(KeyButMask::Shift) != 0 {
+                        self.app.spotlight.toggle_spot();
+                        self.update_screen()?;
+                    } else {
+                        self.app.spotlight.motion((e.event_x, e.event_y), false);
+                        self.switch_tool(H)?;
+                    }
+                }
+                Enter => self.finish_polygon(false)?,
+                F => self.refill(Fill::next_opacity)?,
+                Zero => self.app.marker_counter = 1,
+                A => self.toggle_auto_fade(),
+                Delete => self.delete_selection()?,
+                PageUp => self.reorder_selection(ZOrder::Raise)?,
+                PageDown => self.reorder_selection(ZOrder::Lower)?,
+                Home => self.reorder_selection(ZOrder::Front)?,
+                End => self.reorder_selection(ZOrder::Back)?,
+                F1 | F2 | F3 | F4 => {
+                    if e.state & u16::from(KeyButMask::Shift) != 0 {
+                        self.toggle_layer(e.detail.into())?;
+                    } else {
+                        self.switch_layer(e.detail.into())?;
+                    }
+                }
+                X => self.clear_layer()?,
+                F8 => self.toggle_passthrough()?,
+                F9 => self.toggle_overlay()?,
+                W => {
+                    if e.state & u16::from(KeyButMask::Shift) != 0 {
+                        self.app.background.next_pattern();
+                    } else {
+                        self.app.background.next_board();
+                    }
+                    self.update_screen()?;
+                }
                 _ => {}
             }
         }
         Ok(())
     }
 
-    fn exit(&mut self) {
+    fn exit(&mut self) -> Result {
+        // the daemon keeps the drawings for the next time
+        if self.app.cli.is_daemon {
+            return self.toggle_overlay();
+        }
         self.app.is_running = false;
+        Ok(())
     }
 
-    pub fn draw(&self) -> Result {
+    pub fn draw(&mut self) -> Result {
         if let Event::Expose(e) = self.event {
+            // like the drawings, the background is painted on the last expose
+            if e.count == 0 {
+                self.app.background.expose(
+                    &self.app.conn,
+                    self.app.win_id,
+                    self.app.gc_id,
+                    self.screen_size(),
+                )?;
+            }
             for mov in &self.app.stack {
-                if let Some(mov) = &mov {
-                    self.update_color(Some(mov.color))?;
-                    mov.expose(&self.app.conn, self.app.win_id, self.app.gc_id, &e);
+                if let Some(mov) = mov.as_ref().filter(|m| Layer::shows(&self.app.layers, m)) {
+                    self.update_color(Some((mov.color, mov.width)))?;
+                    mov.expose(
+                        &self.app.conn,
+                        self.app.win_id,
+                        self.app.gc_id,
+                        &e,
+                        &self.app.blending,
+                    )?;
                 }
             }
+            self.app.laser.expose(
+                &self.app.conn,
+                self.app.win_id,
+                self.app.gc_id,
+                &e,
+                &self.app.blending,
+            )?;
+            if self.app.tool == Tool::Spotlight && e.count == 0 {
+                self.app.spotlight.expose(
+                    &self.app.conn,
+                    self.app.win_id,
+                    self.app.gc_id,
+                    self.screen_size(),
+                    &self.app.blending,
+                )?;
+            }
+            if self.app.tool == Tool::Zoom && e.count == 0 {
+                let screen = &self.app.conn.setup().roots[self.app.screen_num];
+                let magnified = self.app.lens.expose(
+                    &self.app.conn,
+                    self.app.win_id,
+                    self.app.gc_id,
+                    (screen.root, self.app.area, self.app.depth),
+                );
+                // the lens goes away rather than pincel, e.g.
+                // on a screen whose pixels it can't copy
+                if let Err(e) = magnified {
+                    println!("Could not magnify the screen: {e}");
+                    self.app.lens.release(&self.app.conn)?;
+                    self.app.tool = self.app.last_tool;
+                }
+            }
+            if e.count == 0 && !self.app.selection.is_empty() {
+                self.app
+                    .conn
+                    .change_gc(self.app.gc_id, &GraphicContext::selection())?;
+                self.app.selection.expose(
+                    &self.app.conn,
+                    self.app.win_id,
+                    self.app.gc_id,
+                    &self.app.stack,
+                )?;
+            }
             self.app.conn.flush()?;
         }
         Ok(())
     }
 
+    fn screen_size(&self) -> (i16, i16) {
+        (
+            i16::try_from(self.app.area.width).unwrap_or(i16::MAX),
+            i16::try_from(self.app.area.height).unwrap_or(i16::MAX),
+        )
+    }
+
     pub fn clear_all_draws(&mut self) -> Result {
         if let Event::ButtonPress(event) = self.event {
             if event.detail == MIDDLE_MOUSE_BUTTON {
+                if !self.app.stack.is_empty() {
+                    self.app.history.record(&self.app.stack);
+                }
+                self.app.vertices.clear();
+                self.app.selection.clear();
                 for _ in 0..self.app.stack.len() {
                     if !self.app.stack.is_empty() {
                         self.app.stack.drain(..);
@@ -82,13 +237,102 @@ impl<C: Connection + Send + Sync> EventHandler<'_, C> {
     pub fn left_click(&mut self) -> Result {
         if let Event::ButtonPress(event) = self.event {
             if event.detail == LEFT_MOUSE_BUTTON {
-                self.update_color(None)?;
-
-                let temp = Some(Movement::new(event, self.app.brush_color.clone().into()));
-                self.app.stack.push(temp);
-                self.app.current = self.app.stack.len() - 1;
-
-                self.app.skip();
+                match self.app.tool {
+                    Tool::Brush => {
+                        self.update_color(None)?;
+                        self.app.history.record(&self.app.stack);
+
+                        let mut temp = Movement::new(
+                            event,
+                            self.app.brush_color.clone().into(),
+                            self.app.line_width,
+                        );
+                        temp.layer = self.app.current_layer;
+                        self.app.stack.push(Some(temp));
+                        self.app.current = self.app.stack.len() - 1;
+
+                        self.app.skip();
+                    }
+                    Tool::Eraser => {
+                        self.app.history.record(&self.app.stack);
+                        self.erase((event.event_x, event.event_y))?;
+                    }
+                    Tool::Marker => {
+                        self.app.history.record(&self.app.stack);
+
+                        let mut marker = Movement::marker(
+                            (event.event_x, event.event_y),
+                            self.app.marker_counter,
+                            self.app.brush_color.clone().into(),
+                            self.app.line_width,
+                        );
+                        marker.layer = self.app.current_layer;
+                        marker.measure(&self.app.conn, self.app.gc_id)?;
+                        self.app.stack.push(Some(marker));
+                        self.app.current = self.app.stack.len() - 1;
+                        self.app.marker_counter += 1;
+                        self.schedule_fade();
+
+                        self.update_screen()?;
+                    }
+                    Tool::Rectangle | Tool::Ellipse => {
+                        self.app.history.record(&self.app.stack);
+
+                        let pos = (event.event_x, event.event_y);
+                        self.app.shape_origin = pos;
+                        let mut shape = Movement::polygon(
+                            self.shape_lines(pos),
+                            self.app.brush_color.clone().into(),
+                            self.app.line_width,
+                        );
+                        shape.layer = self.app.current_layer;
+                        shape.fill = self.app.fill.visible();
+                        self.app.stack.push(Some(shape));
+                        self.app.current = self.app.stack.len() - 1;
+                    }
+                    Tool::Laser => self.app.laser.press(event, self.app.line_width),
+                    Tool::Spotlight => self.app.spotlight.press((event.event_x, event.event_y)),
+                    Tool::Zoom => {}
+                    Tool::Polygon => {
+                        let pos = (event.event_x, event.event_y);
+                        let is_double_click = self.app.vertices.last().map_or(false, |last| {
+                            (last.0 - pos.0).abs() <= DOUBLE_CLICK_DISTANCE
+                                && (last.1 - pos.1).abs() <= DOUBLE_CLICK_DISTANCE
+                                && event.time.wrapping_sub(self.app.last_click) < DOUBLE_CLICK_TIME
+                        });
+                        self.app.last_click = event.time;
+                        if is_double_click {
+                            return self.finish_polygon(true);
+                        }
+
+                        if self.app.vertices.is_empty() {
+                            self.app.history.record(&self.app.stack);
+
+                            let mut outline = Movement::from_lines(
+                                vec![pos],
+                                self.app.brush_color.clone().into(),
+                                self.app.line_width,
+                            );
+                            outline.layer = self.app.current_layer;
+                            self.app.stack.push(Some(outline));
+                            self.app.current = self.app.stack.len() - 1;
+                        }
+                        self.app.vertices.push(pos);
+                        self.preview_polygon(pos);
+                        self.update_screen()?;
+                    }
+                    Tool::Select => {
+                        let extend = event.state & u16::from(KeyButMask::Shift) != 0;
+                        self.app.selection.press(
+                            &self.app.stack,
+                            &self.app.layers,
+                            (event.event_x, event.event_y),
+                            extend,
+                        );
+                        self.app.history.record(&self.app.stack);
+                        self.update_screen()?;
+                    }
+                }
             }
         }
         Ok(())
@@ -97,11 +341,12 @@ impl<C: Connection + Send + Sync> EventHandler<'_, C> {
     pub fn right_click(&mut self) -> Result {
         if let Event::ButtonPress(event) = self.event {
             if event.detail == RIGHT_MOUSE_BUTTON {
-                if self.app.stack.is_empty() {
+                if !self.app.history.undo(&mut self.app.stack) {
                     self.app.skip();
                     return Ok(());
                 }
-                self.app.stack.pop();
+                self.app.vertices.clear();
+                self.app.selection.clear();
                 self.app.current = if !self.app.stack.is_empty() {
                     self.app.stack.len() - 1
                 } else {
@@ -116,11 +361,63 @@ impl<C: Connection + Send + Sync> EventHandler<'_, C> {
     pub fn left_release(&mut self) -> Result {
         if let Event::ButtonRelease(event) = self.event {
             if event.detail == LEFT_MOUSE_BUTTON {
-                self.app.stack[self.app.current]
-                    .as_mut()
-                    .unwrap()
-                    .finish(&self.app.conn, self.app.win_id, self.app.gc_id, &event)
-                    .unwrap();
+                match self.app.tool {
+                    Tool::Brush => {
+                        // the press may have been made with another
+                        // tool, before a key switched to the brush
+                        let current = match self
+                            .app
+                            .stack
+                            .get_mut(self.app.current)
+                            .and_then(Option::as_mut)
+                        {
+                            Some(current) if !current.is_finished() => current,
+                            _ => return Ok(()),
+                        };
+                        current
+                            .finish(&self.app.conn, self.app.win_id, self.app.gc_id, &event)
+                            .unwrap();
+
+                        // freehand loops get filled like the closed shapes
+                        if let Some(fill) = self.app.fill.visible() {
+                            if current.is_closed_loop() {
+                                current.fill = Some(fill);
+                                self.update_screen()?;
+                            }
+                        }
+                        self.schedule_fade();
+                    }
+                    Tool::Rectangle | Tool::Ellipse => {
+                        // a click without dragging leaves nothing to draw
+                        if self.app.shape_origin == (event.event_x, event.event_y) {
+                            self.app.stack.pop();
+                            self.app.current = self.app.stack.len().saturating_sub(1);
+                            self.app.history.discard_unchanged(&self.app.stack);
+                        } else {
+                            self.schedule_fade();
+                        }
+                        self.update_screen()?;
+                    }
+                    Tool::Eraser => {
+                        self.app.eraser.finish();
+                        self.app.history.discard_unchanged(&self.app.stack);
+                    }
+                    Tool::Select => {
+                        self.app
+                            .selection
+                            .release(&self.app.stack, &self.app.layers);
+                        self.app.history.discard_unchanged(&self.app.stack);
+                        self.update_screen()?;
+                    }
+                    Tool::Laser => self.app.laser.release(
+                        &self.app.conn,
+                        self.app.win_id,
+                        self.app.gc_id,
+                        &event,
+                    )?,
+                    Tool::Spotlight => self.app.spotlight.release(),
+                    Tool::Marker | Tool::Polygon | Tool::Zoom => {}
+                }
             }
         }
         Ok(())
@@ -129,15 +426,10 @@ impl<C: Connection + Send + Sync> EventHandler<'_, C> {
     pub fn scroll_wheel_up(&mut self) -> Result {
         if let Event::ButtonPress(event) = self.event {
             if event.detail == SCROLL_WHEEL_UP {
-                let width = &mut self.app.line_width;
-                match width {
-                    LineWidth::Thin => {
-                        *width = LineWidth::Normal;
-                    }
-                    LineWidth::Normal => {
-                        *width = LineWidth::Wide;
-                    }
-                    LineWidth::Wide => {}
+                if self.app.tool == Tool::Zoom {
+                    self.app.lens.zoom_in();
+                } else {
+                    self.restyle(LineWidth::thicker);
                 }
             }
             self.update_screen()?;
@@ -148,15 +440,10 @@ impl<C: Connection + Send + Sync> EventHandler<'_, C> {
     pub fn scroll_wheel_down(&mut self) -> Result {
         if let Event::ButtonPress(event) = self.event {
             if event.detail == SCROLL_WHEEL_DOWN {
-                let width = &mut self.app.line_width;
-                match width {
-                    LineWidth::Thin => {}
-                    LineWidth::Normal => {
-                        *width = LineWidth::Thin;
-                    }
-                    LineWidth::Wide => {
-                        *width = LineWidth::Normal;
-                    }
+                if self.app.tool == Tool::Zoom {
+                    self.app.lens.zoom_out();
+                } else {
+                    self.restyle(LineWidth::thinner);
                 }
             }
             self.update_screen();
@@ -174,12 +461,62 @@ impl<C: Connection + Send + Sync> EventHandler<'_, C> {
     }
 
     pub fn moving(&mut self) -> Result {
-        if self.app.stack.is_empty() {
+        if self.app.stack.is_empty()
+            && !matches!(self.app.tool, Tool::Laser | Tool::Spotlight | Tool::Zoom)
+        {
             return Ok(());
         }
 
         if let Event::MotionNotify(event) = self.event {
-            if let Some(current) = &mut self.app.stack[self.app.current] {
+            let is_dragging = event.state & u16::from(KeyButMask::Button1) != 0;
+            match self.app.tool {
+                Tool::Polygon => {
+                    if !self.app.vertices.is_empty() {
+                        self.preview_polygon((event.event_x, event.event_y));
+                        self.update_screen()?;
+                    }
+                    return Ok(());
+                }
+                Tool::Spotlight => {
+                    let pos = (event.event_x, event.event_y);
+                    if self.app.spotlight.motion(pos, is_dragging) {
+                        self.update_screen()?;
+                    }
+                    return Ok(());
+                }
+                Tool::Zoom => {
+                    self.app.lens.follow((event.event_x, event.event_y));
+                    return self.update_screen();
+                }
+                _ if !is_dragging => return Ok(()),
+                Tool::Laser => {
+                    return Ok(self.app.laser.motion(
+                        &self.app.conn,
+                        self.app.win_id,
+                        self.app.gc_id,
+                        (event.event_x, event.event_y, event.time),
+                    )?);
+                }
+                Tool::Eraser => return self.erase((event.event_x, event.event_y)),
+                Tool::Select => {
+                    let pos = (event.event_x, event.event_y);
+                    if self.app.selection.motion(&mut self.app.stack, pos) {
+                        self.update_screen()?;
+                    }
+                    return Ok(());
+                }
+                Tool::Marker => return Ok(()),
+                Tool::Rectangle | Tool::Ellipse => {
+                    let lines = self.shape_lines((event.event_x, event.event_y));
+                    if let Some(Some(shape)) = self.app.stack.get_mut(self.app.current) {
+                        shape.set_lines(lines);
+                    }
+                    return self.update_screen();
+                }
+                Tool::Brush => {}
+            }
+
+            if let Some(Some(current)) = self.app.stack.get_mut(self.app.current) {
                 current.motion(
                     &self.app.conn,
                     self.app.win_id,
@@ -191,10 +528,37 @@ impl<C: Connection + Send + Sync> EventHandler<'_, C> {
         Ok(())
     }
 
-    fn update_color(&self, with_color: Option<CurrentColor>) -> Result {
+    fn erase(&mut self, pos: (i16, i16)) -> Result {
+        if self
+            .app
+            .eraser
+            .erase(&mut self.app.stack, &self.app.layers, pos)
+        {
+            self.app.current = self.app.stack.len().saturating_sub(1);
+            self.update_screen()?;
+        }
+        Ok(())
+    }
+
+    /// Changes the width of the selected drawings,
+    /// or of the brush when nothing is selected
+    fn restyle(&mut self, change: fn(LineWidth) -> LineWidth) {
+        if self.app.selection.items.is_empty() {
+            self.app.line_width = change(self.app.line_width);
+            return;
+        }
+
+        self.app.history.record(&self.app.stack);
+        for mov in self.app.selection.selected(&mut self.app.stack) {
+            mov.set_width(change(mov.width));
+        }
+        self.app.history.discard_unchanged(&self.app.stack);
+    }
+
+    fn update_color(&self, with_style: Option<(CurrentColor, LineWidth)>) -> Result {
         let new_gc;
-        if let Some(color) = with_color {
-            new_gc = GraphicContext::change_color(color.value(), self.app.line_width);
+        if let Some((color, width)) = with_style {
+            new_gc = GraphicContext::change_color(color.value(), width);
         } else {
             new_gc =
                 GraphicContext::change_color(self.app.brush_color.value(), self.app.line_width);
@@ -205,8 +569,47 @@ impl<C: Connection + Send + Sync> EventHandler<'_, C> {
         Ok(())
     }
 
-    fn switch_color(&self, key: Keys) {
-        let color = match key {
+    /// Changes the color of the selected drawings,
+    /// or of the brush when nothing is selected
+    fn switch_color(&mut self, key: Keys) -> Result {
+        let color = Self::color_name(key);
+
+        if !self.app.selection.items.is_empty() {
+            self.app.history.record(&self.app.stack);
+            for mov in self.app.selection.selected(&mut self.app.stack) {
+                mov.color = CurrentColor::from(color);
+            }
+            self.app.history.discard_unchanged(&self.app.stack);
+            return self.update_screen();
+        }
+
+        let mut brush_color = CurrentColorSingleton::new();
+        brush_color.set(color);
+        Ok(())
+    }
+
+    /// Changes the fill of the selected drawings,
+    /// or of the new shapes when nothing is selected
+    fn refill(&mut self, change: impl Fn(Fill) -> Fill) -> Result {
+        if self.app.selection.items.is_empty() {
+            self.app.fill = change(self.app.fill);
+            return Ok(());
+        }
+
+        self.app.history.record(&self.app.stack);
+        let fill = self.app.fill;
+        for mov in self.app.selection.selected(&mut self.app.stack) {
+            if let Shape::Marker(_) = mov.shape {
+                continue;
+            }
+            mov.fill = change(mov.fill.unwrap_or(fill)).visible();
+        }
+        self.app.history.discard_unchanged(&self.app.stack);
+        self.update_screen()
+    }
+
+    fn color_name(key: Keys) -> &'static str {
+        match key {
             One => "red",
             Two => "blue",
             Three => "yellow",
@@ -214,49 +617,337 @@ impl<C: Connection + Send + Sync> EventHandler<'_, C> {
             Five => "orange",
             Six => "black",
             _ => "",
+        }
+    }
+
+    /// Returns the outline of the shape being dragged from its origin to `pos`
+    fn shape_lines(&self, pos: (i16, i16)) -> Vec<(i16, i16)> {
+        match self.app.tool {
+            Tool::Ellipse => Shape::ellipse(self.app.shape_origin, pos),
+            _ => Shape::rectangle(self.app.shape_origin, pos),
+        }
+    }
+
+    /// Shows the polygon being placed with a segment
+    /// from its last vertex to the cursor
+    fn preview_polygon(&mut self, cursor: (i16, i16)) {
+        let mut lines = self.app.vertices.clone();
+        lines.push(cursor);
+        if let Some(Some(outline)) = self.app.stack.get_mut(self.app.current) {
+            outline.set_lines(lines);
+        }
+    }
+
+    /// Ends the polygon being placed, either closing it into
+    /// a shape that can be filled or leaving it as an open line
+    fn finish_polygon(&mut self, close: bool) -> Result {
+        if self.app.vertices.is_empty() {
+            return Ok(());
+        }
+
+        let vertices = std::mem::take(&mut self.app.vertices);
+        if vertices.len() < 2 {
+            self.app.stack.pop();
+            self.app.current = self.app.stack.len().saturating_sub(1);
+            self.app.history.discard_unchanged(&self.app.stack);
+            return self.update_screen();
+        }
+
+        let fill = self.app.fill.visible();
+        if let Some(Some(outline)) = self.app.stack.get_mut(self.app.current) {
+            if close && vertices.len() > 2 {
+                outline.shape = Shape::Polygon;
+                outline.fill = fill;
+            }
+            outline.set_lines(vertices);
+        }
+        self.schedule_fade();
+        self.update_screen()
+    }
+
+    /// Lets the clicks and the keys reach the windows below,
+    /// keeping the drawings on the screen, or takes them back
+    fn toggle_passthrough(&mut self) -> Result {
+        match self.app.overlay {
+            Overlay::Drawing => {
+                self.finish_polygon(false)?;
+                self.set_input_region(false)?;
+                self.app.conn.set_input_focus(
+                    InputFocus::PointerRoot,
+                    InputFocus::PointerRoot,
+                    CURRENT_TIME,
+                )?;
+                self.app.overlay = Overlay::Passthrough;
+                println!("Clicks go through the drawings");
+            }
+            Overlay::Passthrough => {
+                self.set_input_region(true)?;
+                self.app
+                    .conn
+                    .set_input_focus(InputFocus::Parent, self.app.win_id, CURRENT_TIME)?;
+                self.app.overlay = Overlay::Drawing;
+                println!("Drawing again");
+            }
+            Overlay::Hidden => return Ok(()),
+        }
+        self.app.conn.flush()?;
+        Ok(())
+    }
+
+    fn set_input_region(&mut self, takes_input: bool) -> Result {
+        self.app.conn.xfixes_query_version(5, 0)?.reply()?;
+
+        // an empty input region makes the window transparent to the
+        // pointer, while no region at all restores the whole window
+        if takes_input {
+            self.app
+                .conn
+                .xfixes_set_window_shape_region(self.app.win_id, SK::Input, 0, 0, NONE)?;
+        } else {
+            let region = self.app.conn.generate_id()?;
+            self.app.conn.xfixes_create_region(region, &[])?;
+            self.app.conn.xfixes_set_window_shape_region(
+                self.app.win_id,
+                SK::Input,
+                0,
+                0,
+                region,
+            )?;
+            self.app.conn.xfixes_destroy_region(region)?;
+        }
+        Ok(())
+    }
+
+    /// Hides the overlay keeping the drawings, or shows it again
+    fn toggle_overlay(&mut self) -> Result {
+        if self.app.overlay == Overlay::Hidden {
+            // the screen may have changed while the overlay was hidden
+            if self.app.cli.is_frozen {
+                let screen = &self.app.conn.setup().roots[self.app.screen_num];
+                if let Err(e) = GraphicContext::freeze(
+                    &self.app.conn,
+                    (screen.root, self.app.area),
+                    (self.app.win_id, self.app.depth),
+                    self.app.gc_id,
+                ) {
+                    println!("Could not copy the screen again: {e}");
+                }
+            }
+            self.app.conn.map_window(self.app.win_id)?;
+            self.app
+                .conn
+                .set_input_focus(InputFocus::Parent, self.app.win_id, CURRENT_TIME)?;
+            self.app.overlay = Overlay::Drawing;
+        } else {
+            self.finish_polygon(false)?;
+            if self.app.overlay == Overlay::Passthrough {
+                self.set_input_region(true)?;
+            }
+            self.app.conn.unmap_window(self.app.win_id)?;
+            // the lens copies the screen again once it's shown
+            self.app.lens.release(&self.app.conn)?;
+            self.app.overlay = Overlay::Hidden;
+        }
+        self.app.conn.flush()?;
+        Ok(())
+    }
+
+    fn toggle_auto_fade(&mut self) {
+        self.app.is_auto_fading = !self.app.is_auto_fading;
+        if self.app.is_auto_fading {
+            println!(
+                "New drawings fade after {} seconds",
+                self.app.config.fade_after
+            );
+        } else {
+            println!("New drawings stay on the screen");
+        }
+    }
+
+    /// Makes the drawing just finished disappear
+    /// after a while, when auto-fade is on
+    fn schedule_fade(&mut self) {
+        if !self.app.is_auto_fading {
+            return;
+        }
+        let lifetime = Duration::from_secs(self.app.config.fade_after);
+        if let Some(Some(mov)) = self.app.stack.get_mut(self.app.current) {
+            // a lifetime too long to count up to never ends
+            mov.expires = Instant::now().checked_add(lifetime);
+        }
+    }
+
+    fn switch_tool(&mut self, key: Keys) -> Result {
+        self.finish_polygon(false)?;
+        let tool = match key {
+            E => Tool::Eraser,
+            S => Tool::Select,
+            N => Tool::Marker,
+            R => Tool::Rectangle,
+            C => Tool::Ellipse,
+            G => Tool::Polygon,
+            L => Tool::Laser,
+            // the spotlight and zoom keys turn them off again
+            H if self.app.tool == Tool::Spotlight => self.app.last_tool,
+            H => Tool::Spotlight,
+            Z if self.app.tool == Tool::Zoom => self.app.last_tool,
+            Z => Tool::Zoom,
+            _ => Tool::Brush,
         };
-        let mut brush_color = CurrentColorSingleton::new();
-        brush_color.set(color);
+        let is_overlay = |tool| matches!(tool, Tool::Spotlight | Tool::Zoom);
+        let previous = self.app.tool;
+        // turning an overlay off goes back to a tool that draws
+        if !is_overlay(previous) {
+            self.app.last_tool = previous;
+        }
+        self.app.tool = tool;
+        self.finish_drag();
+
+        if previous == Tool::Zoom && tool != Tool::Zoom {
+            self.app.lens.release(&self.app.conn)?;
+        }
+        if is_overlay(previous) || is_overlay(tool) {
+            self.update_screen()?;
+        }
+
+        if self.app.tool != Tool::Select && !self.app.selection.is_empty() {
+            self.app.selection.clear();
+            self.update_screen()?;
+        }
+        Ok(())
+    }
+
+    fn copy_selection(&mut self) {
+        if !self.app.selection.items.is_empty() {
+            self.app.clipboard = self.app.selection.copy(&self.app.stack);
+        }
     }
 
-    unsafe fn save_ximage(&self, path: &str, image: *mut xlib::XImage, w: u32, h: u32) {
-        if !image.is_null() {
-            let image = &mut *image;
-            let sl: &[Bgr] = {
-                slice::from_raw_parts(
-                    (image).data as *const _,
-                    (image).width as usize * (image).height as usize,
-                )
-            };
+    /// Pastes the copied drawings centered at `(x, y)`
+    fn paste(&mut self, (x, y): (i16, i16)) -> Result {
+        let (left, top, right, bottom) = match Movement::bounds_of(&self.app.clipboard) {
+            Some(bounds) => bounds,
+            None => return Ok(()),
+        };
+        let offset = (x - (left + right) / 2, y - (top + bottom) / 2);
 
-            let mut bgr_iter = sl.iter();
-            let mut image_buffer = RgbImage::new(w, h);
+        let mut movements = self.app.clipboard.clone();
+        for mov in &mut movements {
+            mov.translate(offset);
+            mov.layer = self.app.current_layer;
+        }
+        self.insert(movements)
+    }
 
-            for pix in image_buffer.pixels_mut() {
-                let bgr = bgr_iter.next().unwrap();
-                pix.0 = [bgr.r, bgr.g, bgr.b];
+    fn duplicate_selection(&mut self) -> Result {
+        let mut movements = self.app.selection.copy(&self.app.stack);
+        for mov in &mut movements {
+            mov.translate((DUPLICATE_OFFSET, DUPLICATE_OFFSET));
+        }
+        self.insert(movements)
+    }
+
+    /// Adds the drawings on top of the stack and selects them
+    fn insert(&mut self, movements: Vec<Movement>) -> Result {
+        if movements.is_empty() {
+            return Ok(());
+        }
+
+        self.finish_polygon(false)?;
+        self.app.history.record(&self.app.stack);
+        self.app.selection.clear();
+        for mov in movements {
+            self.app.selection.items.push(self.app.stack.len());
+            self.app.stack.push(Some(mov));
+        }
+        self.app.current = self.app.stack.len() - 1;
+        self.app.tool = Tool::Select;
+        self.finish_drag();
+        self.update_screen()
+    }
+
+    /// Ends the laser trail or the erasing a drag left
+    /// unfinished, as its release goes to the new tool
+    fn finish_drag(&mut self) {
+        self.app.laser.finish();
+        self.app.eraser.finish();
+    }
+
+    /// Makes the layer of the function key the one new drawings go to
+    fn switch_layer(&mut self, key: Keys) -> Result {
+        let index = Self::layer_index(key);
+        if let Some(layer) = self.app.layers.get_mut(index) {
+            self.app.current_layer = index;
+            println!("Drawing on layer {}", layer.name);
+            if !layer.is_visible {
+                layer.is_visible = true;
+                self.update_screen()?;
             }
+        }
+        Ok(())
+    }
 
-            image_buffer.save(path).unwrap();
+    fn toggle_layer(&mut self, key: Keys) -> Result {
+        if let Some(layer) = self.app.layers.get_mut(Self::layer_index(key)) {
+            layer.is_visible = !layer.is_visible;
+            self.app.selection.clear();
+            self.update_screen()?;
         }
+        Ok(())
     }
 
-    fn copy_desktop_image(&self, path: &str) {
-        unsafe {
-            let dis = xlib::XOpenDisplay(std::ptr::null::<i8>());
-            let scr = xlib::XDefaultScreenOfDisplay(dis);
-            let drawable = xlib::XDefaultRootWindow(dis);
-            let w = (*scr).width as u32;
-            let h = (*scr).height as u32;
+    /// Removes every drawing of the current layer
+    fn clear_layer(&mut self) -> Result {
+        let layer = self.app.current_layer;
+        if !self
+            .app
+            .stack
+            .iter()
+            .flatten()
+            .any(|mov| mov.layer == layer)
+        {
+            return Ok(());
+        }
 
-            let image =
-                xlib::XGetImage(dis, drawable, 0, 0, w, h, xlib::XAllPlanes(), xlib::ZPixmap);
+        self.app.history.record(&self.app.stack);
+        self.app.vertices.clear();
+        self.app.selection.clear();
+        self.app
+            .stack
+            .retain(|mov| mov.as_ref().map_or(true, |m| m.layer != layer));
+        self.app.current = self.app.stack.len().saturating_sub(1);
+        self.update_screen()
+    }
 
-            self.save_ximage(path, image, w, h);
+    fn layer_index(key: Keys) -> usize {
+        match key {
+            F2 => 1,
+            F3 => 2,
+            F4 => 3,
+            _ => 0,
+        }
+    }
 
-            xlib::XDestroyImage(image);
-            xlib::XCloseDisplay(dis);
+    fn reorder_selection(&mut self, order: ZOrder) -> Result {
+        if self.app.selection.items.is_empty() {
+            return Ok(());
         }
+
+        self.app.history.record(&self.app.stack);
+        self.app.selection.reorder(&mut self.app.stack, order);
+        self.app.history.discard_unchanged(&self.app.stack);
+        self.update_screen()
+    }
+
+    fn delete_selection(&mut self) -> Result {
+        if self.app.selection.items.is_empty() {
+            return Ok(());
+        }
+
+        self.app.history.record(&self.app.stack);
+        self.app.selection.delete(&mut self.app.stack);
+        self.app.current = self.app.stack.len().saturating_sub(1);
+        self.update_screen()
     }
 
     fn save_screenshot(&self) -> Result {
@@ -268,15 +959,54 @@ impl<C: Connection + Send + Sync> EventHandler<'_, C> {
             .collect::<Vec<&str>>()[0]
             .to_string();
 
-        let path_str = self.app.config.screenshot_dir.as_str().to_owned()
-            + "Screenshot from "
-            + &current_date_time
-            + ".png";
+        let path = home
+            .join(&self.app.config.screenshot_dir)
+            .join(format!("Screenshot from {current_date_time}.png"));
 
-        let path = home.join(std::path::PathBuf::from(path_str));
+        copy_desktop_image(path.to_str().unwrap())
+    }
+}
 
-        self.copy_desktop_image(path.to_str().unwrap());
+unsafe fn save_ximage(path: &str, image: *mut xlib::XImage, w: u32, h: u32) -> Result {
+    if !image.is_null() {
+        let image = &mut *image;
+        let sl: &[Bgr] = {
+            slice::from_raw_parts(
+                (image).data as *const _,
+                (image).width as usize * (image).height as usize,
+            )
+        };
 
-        Ok(())
+        let mut bgr_iter = sl.iter();
+        let mut image_buffer = RgbImage::new(w, h);
+
+        for pix in image_buffer.pixels_mut() {
+            let bgr = bgr_iter.next().unwrap();
+            pix.0 = [bgr.r, bgr.g, bgr.b];
+        }
+
+        image_buffer
+            .save(path)
+            .map_err(|e| PincelError::GenericError(Box::new(e)))?;
+    }
+    Ok(())
+}
+
+/// Saves what's on the screen to a png file
+pub fn copy_desktop_image(path: &str) -> Result {
+    unsafe {
+        let dis = xlib::XOpenDisplay(std::ptr::null::<i8>());
+        let scr = xlib::XDefaultScreenOfDisplay(dis);
+        let drawable = xlib::XDefaultRootWindow(dis);
+        let w = (*scr).width as u32;
+        let h = (*scr).height as u32;
+
+        let image = xlib::XGetImage(dis, drawable, 0, 0, w, h, xlib::XAllPlanes(), xlib::ZPixmap);
+
+        let saved = save_ximage(path, image, w, h);
+
+        xlib::XDestroyImage(image);
+        xlib::XCloseDisplay(dis);
+        saved
     }
 }
@@ -13,6 +13,7 @@ pub struct WindowBuilder<'wp> {
     pos: (&'wp i16, &'wp i16),
     size: (&'wp u16, &'wp u16),
     visual: Option<&'wp Visualtype>,
+    depth: u8,
     win_params: Option<CreateWindowAux>,
 }
 
@@ -24,6 +25,7 @@ impl<'wp> WindowBuilder<'wp> {
             pos: (&0, &0),
             size: (&0, &0),
             visual: None,
+            depth: 32,
             win_params: None,
         }
     }
@@ -31,7 +33,7 @@ impl<'wp> WindowBuilder<'wp> {
         let (win_start_x, win_start_y) = self.pos;
         let (width, height) = self.size;
         conn.create_window(
-            32,
+            self.depth,
             self.win_id,
             self.screen.unwrap().root,
             *win_start_x,
@@ -66,6 +68,10 @@ impl<'wp> WindowBuilder<'wp> {
         self.visual = Some(visual);
         self
     }
+    pub fn with_depth(&mut self, depth: u8) -> &mut Self {
+        self.depth = depth;
+        self
+    }
     pub fn with_win_params(&mut self, colormap: u32) -> &mut Self {
         let win_params = CreateWindowAux::new()
             .event_mask(
@@ -75,6 +81,7 @@ impl<'wp> WindowBuilder<'wp> {
                     | EventMask::ButtonMotion
                     | EventMask::ButtonRelease
                     | EventMask::Button1Motion
+                    | EventMask::PointerMotion
                     | EventMask::EnterWindow
                     | EventMask::KeyPress,
             )
@@ -13,10 +13,43 @@ mod commands;
 mod domain;
 mod gui;
 
-use application::{app, app_initializer};
+use application::{
+    app, app_initializer,
+    cli::{self, Action, Cli},
+    client,
+    lock::Lock,
+};
 use domain::{event_handler, Result};
+use std::process;
 
 fn main() -> Result {
-    let mut app = app_initializer::init()?;
-    Ok(app.run()?)
+    let cli = match Cli::new() {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("pincel: {e}\nTry 'pincel --help' for more information.");
+            process::exit(client::USAGE_ERROR);
+        }
+    };
+
+    match &cli.action {
+        Action::Start => {
+            // held until pincel exits, a second one
+            // shows the first one instead
+            let _lock = match Lock::acquire() {
+                Ok(Some(lock)) => Some(lock),
+                Ok(None) => {
+                    println!("pincel is already running, showing it");
+                    process::exit(client::request("show"));
+                }
+                Err(e) => {
+                    println!("Could not check for another pincel: {e}");
+                    None
+                }
+            };
+            app_initializer::init(cli)?.run()?;
+        }
+        Action::Send(line) => process::exit(client::request(line)),
+        Action::Help => println!("{}", cli::USAGE),
+    }
+    Ok(())
 }